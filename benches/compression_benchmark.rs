@@ -0,0 +1,172 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rlzav::checksum::ChecksumKind;
+use rlzav::{
+    compress_frame_with, compress_large, compress_large_bound, compress_to_vec, decompress_blocks,
+    decompress_frame, decompress_to_vec,
+};
+
+const SIZES: [usize; 4] = [4 << 10, 64 << 10, 1 << 20, 8 << 20];
+
+const WORDS: &[&str] = &[
+    "the", "of", "and", "stream", "block", "match", "offset", "literal", "length",
+    "window", "hash", "table", "compress", "decompress", "frame", "buffer", "a", "to",
+];
+
+// Incompressible bytes
+fn random(len: usize) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(0x4C5A_4156);
+    let mut data = vec![0u8; len];
+    rng.fill(&mut data[..]);
+    data
+}
+
+// Words drawn from a small vocabulary, with punctuation and line breaks
+fn text(len: usize) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(0x4C5A_4156);
+    let mut data = Vec::with_capacity(len + 16);
+    while data.len() < len {
+        data.extend_from_slice(WORDS.choose(&mut rng).unwrap().as_bytes());
+        data.push(match rng.gen_range(0..12) {
+            0 => b'\n',
+            1 => b',',
+            _ => b' ',
+        });
+    }
+    data.truncate(len);
+    data
+}
+
+// A short record repeated end to end
+fn repetitive(len: usize) -> Vec<u8> {
+    b"id=0042;status=ok;".iter().copied().cycle().take(len).collect()
+}
+
+type Generator = fn(usize) -> Vec<u8>;
+
+const KINDS: [(&str, Generator); 3] =
+    [("random", random), ("text", text), ("repetitive", repetitive)];
+
+fn compression_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compress");
+    for (kind, generate) in KINDS {
+        for size in SIZES {
+            let data = generate(size);
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(BenchmarkId::new(kind, size), &data, |b, data| {
+                b.iter(|| compress_to_vec(data).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn decompression_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decompress");
+    for (kind, generate) in KINDS {
+        for size in SIZES {
+            let compressed = compress_to_vec(&generate(size)).unwrap();
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(BenchmarkId::new(kind, size), &compressed, |b, compressed| {
+                b.iter(|| decompress_to_vec(compressed, size).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+// Single-shot decompression against the block table path on an input that
+// fits one window, so the difference is the per-block overhead
+fn block_decompression_benchmark(c: &mut Criterion) {
+    let size = 8_000_000;
+    let data = repetitive(size);
+    let single = compress_to_vec(&data).unwrap();
+    let mut blocks = vec![0u8; compress_large_bound(size)];
+    let blocks_len = compress_large(&data, &mut blocks).unwrap();
+    blocks.truncate(blocks_len);
+
+    let mut group = c.benchmark_group("decompress_8mb");
+    group.throughput(Throughput::Bytes(size as u64));
+    group.bench_function("single_shot", |b| b.iter(|| decompress_to_vec(&single, size).unwrap()));
+    group.bench_function("decompress_blocks", |b| {
+        b.iter(|| {
+            let mut out = vec![0u8; size];
+            decompress_blocks(&blocks, &mut out).unwrap();
+            out
+        })
+    });
+    group.finish();
+}
+
+// An incompressible frame is stored, so decoding it is a copy; the forced
+// fmt2 stream of the same data shows what running the decoder costs. The
+// frame skips its checksum so only the body decoding is compared
+fn stored_frame_benchmark(c: &mut Criterion) {
+    let size = 1 << 20;
+    let data = random(size);
+    let frame = compress_frame_with(&data, ChecksumKind::None);
+    let stream = compress_to_vec(&data).unwrap();
+
+    let mut group = c.benchmark_group("decompress_random_1mb");
+    group.throughput(Throughput::Bytes(size as u64));
+    group.bench_function("stored_frame", |b| b.iter(|| decompress_frame(&frame).unwrap()));
+    group.bench_function("fmt2_stream", |b| b.iter(|| decompress_to_vec(&stream, size).unwrap()));
+    group.finish();
+}
+
+// The same inputs through each backend, side by side per input kind. Both
+// decoders read the C encoder's stream, so they decode identical bytes
+#[cfg(all(feature = "c-backend", feature = "rust-backend"))]
+fn backend_benchmark(c: &mut Criterion) {
+    use rlzav::{c as c_backend, compress_bound, rust as rust_backend};
+
+    type Codec = fn(&[u8], &mut [u8]) -> i32;
+    const BACKENDS: [(&str, Codec, Codec); 2] = [
+        ("c", c_backend::compress_default, c_backend::decompress),
+        ("rust", rust_backend::compress_default, rust_backend::decompress),
+    ];
+
+    for (kind, generate) in KINDS {
+        let mut compress_group = c.benchmark_group(format!("backend_compress_{kind}"));
+        for size in [64 << 10, 1 << 20] {
+            let data = generate(size);
+            let mut compressed = vec![0u8; compress_bound(size as i32) as usize];
+            compress_group.throughput(Throughput::Bytes(size as u64));
+            for (backend, compress, _) in BACKENDS {
+                compress_group.bench_with_input(BenchmarkId::new(backend, size), &data, |b, data| {
+                    b.iter(|| compress(data, &mut compressed))
+                });
+            }
+        }
+        compress_group.finish();
+
+        let mut decompress_group = c.benchmark_group(format!("backend_decompress_{kind}"));
+        for size in [64 << 10, 1 << 20] {
+            let compressed = compress_to_vec(&generate(size)).unwrap();
+            let mut out = vec![0u8; size];
+            decompress_group.throughput(Throughput::Bytes(size as u64));
+            for (backend, _, decompress) in BACKENDS {
+                decompress_group.bench_with_input(BenchmarkId::new(backend, size), &compressed, |b, compressed| {
+                    b.iter(|| assert_eq!(decompress(compressed, &mut out), size as i32))
+                });
+            }
+        }
+        decompress_group.finish();
+    }
+}
+
+// Comparing backends needs both of them built
+#[cfg(not(all(feature = "c-backend", feature = "rust-backend")))]
+fn backend_benchmark(_c: &mut Criterion) {}
+
+criterion_group!(
+    benches,
+    compression_benchmark,
+    decompression_benchmark,
+    block_decompression_benchmark,
+    stored_frame_benchmark,
+    backend_benchmark
+);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Arbitrary streams must only ever produce `Ok` or `Err`, never a panic or an
+// out-of-bounds access. The first two bytes pick the destination size.
+fuzz_target!(|data: &[u8]| {
+    let Some((len, src)) = data.split_first_chunk::<2>() else {
+        return;
+    };
+    let mut dst = vec![0u8; u16::from_le_bytes(*len) as usize];
+
+    let _ = rlzav::try_decompress(src, &mut dst);
+    let _ = rlzav::decompress_partial_counts(src, &mut dst);
+    let _ = rlzav::decompress_frame(src);
+    let _ = rlzav::decompress_large(src, &mut dst);
+});
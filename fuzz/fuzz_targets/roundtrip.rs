@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Any input must compress into a `compress_bound` buffer and decode back to itself
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+
+    let mut compressed = vec![0u8; rlzav::compress_bound(data.len() as i32) as usize];
+    let compressed_len = rlzav::compress(data, &mut compressed).expect("compress failed");
+
+    let mut decompressed = vec![0u8; data.len()];
+    let result = rlzav::try_decompress(&compressed[..compressed_len], &mut decompressed);
+    assert_eq!(result, Ok(data.len()));
+    assert_eq!(decompressed, data);
+});
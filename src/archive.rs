@@ -0,0 +1,222 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::errors::LzavError;
+use crate::frame::{compress_frame, decompress_frame};
+
+/// Magic bytes closing every archive written by [`LzavArchiveWriter`].
+pub const ARCHIVE_MAGIC: [u8; 4] = *b"LZAR";
+
+// Index entry: offset, compressed length and original length, all u64 LE
+const INDEX_ENTRY_LEN: usize = 24;
+// Trailer: index offset (u64 LE), entry count (u64 LE), magic
+const TRAILER_LEN: usize = 8 + 8 + 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IndexEntry {
+    offset: u64,
+    compressed_len: u64,
+    original_len: u64,
+}
+
+/// Writer for a random-access archive of independently compressed entries.
+///
+/// Each entry is stored as a frame (see [`compress_frame`]), and
+/// [`finish`](Self::finish) appends an index of
+/// `[offset: u64le][compressed_len: u64le][original_len: u64le]` entries and
+/// a trailer of `[index_offset: u64le][entry_count: u64le][b"LZAR"]`.
+/// Read it back with [`LzavArchive`].
+pub struct LzavArchiveWriter<W: Write> {
+    inner: W,
+    offset: u64,
+    index: Vec<IndexEntry>,
+}
+
+impl<W: Write> LzavArchiveWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            index: Vec::new(),
+        }
+    }
+
+    /// Compress `data` as the next entry, returning its index.
+    pub fn add_entry(&mut self, data: &[u8]) -> io::Result<usize> {
+        let frame = compress_frame(data);
+        self.inner.write_all(&frame)?;
+
+        self.index.push(IndexEntry {
+            offset: self.offset,
+            compressed_len: frame.len() as u64,
+            original_len: data.len() as u64,
+        });
+        self.offset += frame.len() as u64;
+        Ok(self.index.len() - 1)
+    }
+
+    /// Write the index and trailer and return the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        for entry in &self.index {
+            self.inner.write_all(&entry.offset.to_le_bytes())?;
+            self.inner.write_all(&entry.compressed_len.to_le_bytes())?;
+            self.inner.write_all(&entry.original_len.to_le_bytes())?;
+        }
+        self.inner.write_all(&self.offset.to_le_bytes())?;
+        self.inner.write_all(&(self.index.len() as u64).to_le_bytes())?;
+        self.inner.write_all(&ARCHIVE_MAGIC)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// Random-access reader for archives written by [`LzavArchiveWriter`].
+///
+/// Opening reads only the index; [`read_entry`](Self::read_entry) seeks to a
+/// single entry and decompresses just that one.
+pub struct LzavArchive<R: Read + Seek> {
+    inner: R,
+    index: Vec<IndexEntry>,
+}
+
+impl<R: Read + Seek> LzavArchive<R> {
+    /// Read and validate the index at the end of `inner`.
+    pub fn open(mut inner: R) -> io::Result<Self> {
+        let end = inner.seek(SeekFrom::End(0))?;
+        let index_end = end.checked_sub(TRAILER_LEN as u64).ok_or(LzavError::SourceOutOfBounds)?;
+
+        let mut trailer = [0u8; TRAILER_LEN];
+        inner.seek(SeekFrom::Start(index_end))?;
+        inner.read_exact(&mut trailer)?;
+        if trailer[16..] != ARCHIVE_MAGIC {
+            return Err(LzavError::UnknownFormat.into());
+        }
+
+        let index_offset = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+        let count = u64::from_le_bytes(trailer[8..16].try_into().unwrap());
+
+        // The index must fill the space between the entries and the trailer
+        let index_len = count.checked_mul(INDEX_ENTRY_LEN as u64);
+        if index_offset > index_end || index_len != Some(index_end - index_offset) {
+            return Err(LzavError::SourceOutOfBounds.into());
+        }
+
+        let mut table = vec![0u8; (index_end - index_offset) as usize];
+        inner.seek(SeekFrom::Start(index_offset))?;
+        inner.read_exact(&mut table)?;
+
+        let index = table
+            .chunks_exact(INDEX_ENTRY_LEN)
+            .map(|entry| {
+                let entry = IndexEntry {
+                    offset: u64::from_le_bytes(entry[..8].try_into().unwrap()),
+                    compressed_len: u64::from_le_bytes(entry[8..16].try_into().unwrap()),
+                    original_len: u64::from_le_bytes(entry[16..].try_into().unwrap()),
+                };
+                match entry.offset.checked_add(entry.compressed_len) {
+                    Some(end) if end <= index_offset => Ok(entry),
+                    _ => Err(LzavError::SourceOutOfBounds),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { inner, index })
+    }
+
+    /// Number of entries in the archive.
+    pub fn entry_count(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Decompressed length of entry `i`, `None` when it is out of range.
+    pub fn entry_len(&self, i: usize) -> Option<u64> {
+        self.index.get(i).map(|entry| entry.original_len)
+    }
+
+    /// Seek to entry `i` and decompress it. Corrupt entries are reported as
+    /// `InvalidData` errors wrapping the [`LzavError`].
+    pub fn read_entry(&mut self, i: usize) -> io::Result<Vec<u8>> {
+        let entry = *self
+            .index
+            .get(i)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "archive entry out of range"))?;
+
+        let mut frame = vec![0u8; entry.compressed_len as usize];
+        self.inner.seek(SeekFrom::Start(entry.offset))?;
+        self.inner.read_exact(&mut frame)?;
+
+        let data = decompress_frame(&frame)?;
+        if data.len() as u64 != entry.original_len {
+            return Err(LzavError::DestLengthMismatch.into());
+        }
+        Ok(data)
+    }
+
+    /// Consume the archive and return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn records() -> Vec<Vec<u8>> {
+        (0..20)
+            .map(|i| format!("record {i}: ").repeat(i * 7).into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_archive_random_access() {
+        let records = records();
+        let mut writer = LzavArchiveWriter::new(Vec::new());
+        for (i, record) in records.iter().enumerate() {
+            assert_eq!(writer.add_entry(record).unwrap(), i);
+        }
+        let bytes = writer.finish().unwrap();
+
+        let mut archive = LzavArchive::open(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.entry_count(), records.len());
+        for i in [13, 0, 19, 5, 5] {
+            assert_eq!(archive.entry_len(i), Some(records[i].len() as u64));
+            assert_eq!(archive.read_entry(i).unwrap(), records[i]);
+        }
+        assert_eq!(archive.read_entry(20).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        let empty = LzavArchiveWriter::new(Vec::new()).finish().unwrap();
+        assert_eq!(LzavArchive::open(Cursor::new(empty)).unwrap().entry_count(), 0);
+    }
+
+    #[test]
+    fn test_archive_rejects_bad_input() {
+        let mut writer = LzavArchiveWriter::new(Vec::new());
+        for record in records() {
+            writer.add_entry(&record).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        let mut bad_magic = bytes.clone();
+        *bad_magic.last_mut().unwrap() = b'X';
+        assert!(LzavArchive::open(Cursor::new(bad_magic)).is_err());
+
+        // Truncation shifts the trailer, so the index no longer lines up
+        assert!(LzavArchive::open(Cursor::new(&bytes[1..])).is_err());
+        assert!(LzavArchive::open(Cursor::new(&bytes[..10])).is_err());
+
+        let mut huge_count = bytes.clone();
+        let count_at = bytes.len() - 12;
+        huge_count[count_at..count_at + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(LzavArchive::open(Cursor::new(huge_count)).is_err());
+
+        // A corrupt entry only fails that entry
+        let mut corrupt = bytes.clone();
+        let archive = LzavArchive::open(Cursor::new(&bytes)).unwrap();
+        let entry = archive.index[3];
+        corrupt[(entry.offset + entry.compressed_len) as usize - 1] ^= 0x20;
+        let mut archive = LzavArchive::open(Cursor::new(corrupt)).unwrap();
+        assert_eq!(archive.read_entry(3).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(archive.read_entry(4).unwrap(), records()[4]);
+    }
+}
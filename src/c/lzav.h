@@ -41,6 +41,10 @@
 #include <stdlib.h>
 #include <stdbool.h>
 
+#if defined(__SSE2__) || defined(__AVX2__) || defined(__AVX512F__)
+    #include <immintrin.h> // For the SIMD match length helpers.
+#endif
+
 // Consolidate version and format definitions
 #define LZAV_API_VER 0x106
 #define LZAV_VER_STR "4.5"
@@ -1800,6 +1804,8 @@ static inline int lzav_compress_hi( const void* const src, void* const dst,
 			}
 		}
 
+		d = (size_t) ( ip - wp ); // Distance of the best match, if any.
+
 		if(( rc == 0 ) | ( d > 273 ))
 		{
 			// Update a matching entry which is not inside max reference
@@ -2552,11 +2558,16 @@ static inline int32_t lzav_decompress(const void* const src, void* const dst,
 
     // Fast path for tiny data
     const uint8_t* ip = (const uint8_t*)src;
-    if (srcl <= LZAV_TINY_MAX + 2 && ip[0] >> 4 == LZAV_FMT_CUR) {
+    // Tiny streams are exactly the prefix, the length and the raw bytes
+    if (srcl >= 2 && srcl <= LZAV_TINY_MAX + 2 && ip[0] >> 4 == LZAV_FMT_CUR
+        && srcl == ip[1] + 2) {
         const int len = ip[1];
-        if (len <= LZAV_TINY_MAX && len <= dstl) {
+        if (len <= LZAV_TINY_MAX) {
+            if ((size_t) len > dstl) {
+                return LZAV_E_DSTOOB;
+            }
             lzav_memcpy(dst, ip + 2, len);
-            return len;
+            return (size_t) len == dstl ? len : LZAV_E_DSTLEN;
         }
     }
 
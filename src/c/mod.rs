@@ -1,4 +1,10 @@
-use std::os::raw::{c_int, c_void};
+use alloc::vec;
+use core::ffi::{c_int, c_void};
+use core::mem::MaybeUninit;
+
+use crate::errors::{LzavError, LZAV_E_DSTLEN};
+use crate::sink::Sink;
+use crate::CompressLevel;
 
 extern "C" {
     pub fn c_lzav_compress_default(
@@ -8,6 +14,22 @@ extern "C" {
         dstl: c_int,
     ) -> c_int;
 
+    pub fn c_lzav_compress(
+        src: *const c_void,
+        dst: *mut c_void,
+        srcl: c_int,
+        dstl: c_int,
+        ext_buf: *mut c_void,
+        ext_bufl: c_int,
+    ) -> c_int;
+
+    pub fn c_lzav_compress_hi(
+        src: *const c_void,
+        dst: *mut c_void,
+        srcl: c_int,
+        dstl: c_int,
+    ) -> c_int;
+
     pub fn c_lzav_compress_bound(srcl: c_int) -> c_int;
 
     pub fn c_lzav_decompress(
@@ -45,11 +67,111 @@ pub fn compress_default(src: &[u8], dst: &mut [u8]) -> i32 {
     }
 }
 
-pub fn compress_bound(srcl: i32) -> i32 {
-    unsafe { c_lzav_compress_bound(srcl) }
+/// Minimum scratch size in bytes for `compress_with_scratch` to use the
+/// caller's buffer for `srcl` input bytes. `lzav_compress` grows its hash
+/// table to 4 x `srcl` (a power of two, at most 1 MiB) and ignores buffers no
+/// larger than its 16 KiB on-stack table.
+pub fn calculate_hash_table_size(srcl: i32) -> usize {
+    (srcl.max(1) as usize * 4).next_power_of_two().clamp(32 << 10, 1 << 20)
+}
+
+/// Compress with a caller-provided hash table in `scratch`, falling back to
+/// internal allocation when it is smaller than `calculate_hash_table_size`.
+/// Only the largest power-of-two run of 4-byte aligned words in `scratch`
+/// counts, so an unaligned buffer of exactly that size also falls back.
+/// The output is the same either way.
+/// [`AlignedBuffer::for_scratch`](crate::AlignedBuffer::for_scratch)
+/// allocates a suitable buffer.
+pub fn compress_with_scratch(src: &[u8], dst: &mut [u8], scratch: &mut [u8]) -> i32 {
+    // `lzav_compress` needs a 4-byte aligned, power-of-two sized table
+    // SAFETY: every bit pattern is a valid u32
+    let (_, words, _) = unsafe { scratch.align_to_mut::<u32>() };
+    let usable = match words.len() * 4 {
+        0 => 0,
+        len => (1usize << len.ilog2()).min(1 << 30),
+    };
+
+    lzav_debug!(
+        "scratch of {} usable bytes, {} needed for {} bytes",
+        usable,
+        calculate_hash_table_size(src.len() as i32),
+        src.len()
+    );
+    let (ext_buf, ext_bufl) = if usable >= calculate_hash_table_size(src.len() as i32) {
+        (words.as_mut_ptr() as *mut c_void, usable as i32)
+    } else {
+        (core::ptr::null_mut(), 0)
+    };
+
+    unsafe {
+        c_lzav_compress(
+            src.as_ptr() as *const _,
+            dst.as_mut_ptr() as *mut _,
+            src.len() as i32,
+            dst.len() as i32,
+            ext_buf,
+            ext_bufl,
+        )
+    }
 }
 
+/// Same value as `lzav_compress_bound`, computed without the signed
+/// overflow the C version hits near `i32::MAX`, which saturates instead.
+pub const fn compress_bound(srcl: i32) -> i32 {
+    let srcl = if srcl < 0 { 0 } else { srcl as usize };
+    match crate::compress_bound_usize(srcl) {
+        Some(bound) if bound <= i32::MAX as usize => bound as i32,
+        _ => i32::MAX,
+    }
+}
+
+/// Same value as `lzav_compress_bound_hi`, the destination size
+/// `lzav_compress_hi` requires, or `None` past `i32::MAX`.
+const fn compress_bound_hi(srcl: usize) -> Option<usize> {
+    let l2 = srcl / (16 + 5);
+    let bound = (srcl - l2 * 5).div_ceil(16) * 2 - l2 + srcl + 16;
+    if bound <= i32::MAX as usize {
+        Some(bound)
+    } else {
+        None
+    }
+}
+
+/// Compress with `lzav_compress_hi` into `dst`, which must hold
+/// `compress_bound_hi` bytes; 0 on failure.
+fn compress_hi(src: &[u8], dst: &mut [u8]) -> i32 {
+    unsafe {
+        c_lzav_compress_hi(
+            src.as_ptr() as *const _,
+            dst.as_mut_ptr() as *mut _,
+            src.len() as i32,
+            dst.len() as i32,
+        )
+    }
+}
+
+/// Decompress `src` into `dst`, returning the decompressed length or a
+/// negative `LZAV_E_*` code. Streams stored by `compress_or_store` are copied.
 pub fn decompress(src: &[u8], dst: &mut [u8]) -> i32 {
+    // `lzav_decompress` reads the format byte before checking the length
+    if src.is_empty() {
+        return 0;
+    }
+    if dst.is_empty() {
+        return LzavError::InvalidParams.code();
+    }
+    if let Some(payload) = crate::stored_payload(src) {
+        let Some(out) = dst.get_mut(..payload.len()) else {
+            return LzavError::DestOutOfBounds.code();
+        };
+        out.copy_from_slice(payload);
+        // Held to the same exact length as `lzav_decompress` output
+        if dst.len() != payload.len() {
+            return LZAV_E_DSTLEN;
+        }
+        return payload.len() as i32;
+    }
+
     unsafe {
         c_lzav_decompress(
             src.as_ptr() as *const _,
@@ -61,6 +183,21 @@ pub fn decompress(src: &[u8], dst: &mut [u8]) -> i32 {
 }
 
 pub fn decompress_partial(src: &[u8], dst: &mut [u8]) -> i32 {
+    if src.is_empty() {
+        return 0;
+    }
+    if let Some(payload) = crate::stored_payload(src) {
+        let n = payload.len().min(dst.len());
+        dst[..n].copy_from_slice(&payload[..n]);
+        return n as i32;
+    }
+    // `lzav_decompress_partial` only decodes block streams, not the tiny layout
+    if src[0] >> 4 == crate::LZAV_FMT_CUR && crate::scan::is_tiny_stream(src) {
+        let n = (src[1] as usize).min(dst.len());
+        dst[..n].copy_from_slice(&src[2..2 + n]);
+        return n as i32;
+    }
+
     unsafe {
         c_lzav_decompress_partial(
             src.as_ptr() as *const _,
@@ -70,3 +207,147 @@ pub fn decompress_partial(src: &[u8], dst: &mut [u8]) -> i32 {
         )
     }
 }
+
+/// Compress `src` into `dst`, returning the number of bytes written. Empty
+/// input compresses to an empty stream, which decompresses to nothing.
+pub fn compress(src: &[u8], dst: &mut [u8]) -> Result<usize, LzavError> {
+    if src.is_empty() {
+        return Ok(0);
+    }
+    if dst.is_empty() {
+        return Err(LzavError::InvalidParams);
+    }
+
+    if dst.len() < compress_bound(src.len() as i32) as usize {
+        return Err(LzavError::DestOutOfBounds);
+    }
+
+    // The C compressor reports every failure as a zero-length result
+    match compress_default(src, dst) {
+        len if len > 0 => Ok(len as usize),
+        _ => Err(LzavError::InvalidParams),
+    }
+}
+
+/// Compress `src` and push the stream to `sink` in one piece, returning its
+/// length; a sink that fails receives nothing, and a failed compression is
+/// returned as the sink's error. `lzav_compress_default` writes a whole
+/// buffer, so the stream is built in a temporary `Vec` before it is pushed
+/// rather than written through the sink. See [`Sink`] for the destinations
+/// it takes.
+pub fn compress_into<S: Sink>(src: &[u8], mut sink: S) -> Result<usize, S::Error>
+where
+    S::Error: From<LzavError>,
+{
+    let stream = crate::compress_to_vec(src)?;
+    sink.push_bytes(&stream)?;
+    Ok(stream.len())
+}
+
+/// Decompress `src` into possibly uninitialized `dst`, returning the number
+/// of bytes written. `lzav_decompress` only reads back bytes it has already
+/// written, so on success the first `len` bytes of `dst` are initialized.
+pub fn decompress_into_uninit(src: &[u8], dst: &mut [MaybeUninit<u8>]) -> Result<usize, LzavError> {
+    if src.is_empty() {
+        return Ok(0);
+    }
+    if dst.is_empty() {
+        return Err(LzavError::InvalidParams);
+    }
+    if let Some(payload) = crate::stored_payload(src) {
+        let dst = dst.get_mut(..payload.len()).ok_or(LzavError::DestOutOfBounds)?;
+        dst.write_copy_of_slice(payload);
+        return Ok(payload.len());
+    }
+
+    LzavError::check(unsafe {
+        c_lzav_decompress(
+            src.as_ptr() as *const _,
+            dst.as_mut_ptr() as *mut _,
+            src.len() as i32,
+            dst.len() as i32,
+        )
+    })
+}
+
+/// Compress `src` into `dst`, returning the number of bytes written. `Max`
+/// uses `lzav_compress_hi`, which needs more room than `compress_bound`, so
+/// a smaller `dst` gets the stream through a temporary buffer, or the
+/// default stream when the higher-ratio one does not fit. `Fast` and
+/// `Default` both use `lzav_compress_default`.
+pub fn compress_level(src: &[u8], dst: &mut [u8], level: CompressLevel) -> Result<usize, LzavError> {
+    if level != CompressLevel::Max || src.is_empty() || dst.is_empty() {
+        return compress(src, dst);
+    }
+    let Some(bound) = compress_bound_hi(src.len()) else {
+        return compress(src, dst);
+    };
+
+    if dst.len() >= bound {
+        let len = compress_hi(src, dst);
+        if len > 0 {
+            return Ok(len as usize);
+        }
+    } else {
+        let mut buf = vec![0u8; bound];
+        let len = compress_hi(src, &mut buf) as usize;
+        if len > 0 && len <= dst.len() {
+            dst[..len].copy_from_slice(&buf[..len]);
+            return Ok(len);
+        }
+    }
+    compress(src, dst)
+}
+
+/// Decompress `src` into `dst`, returning the number of bytes written. Never
+/// touches the heap: `lzav_decompress` writes only into `dst`, which may be
+/// arena or stack memory.
+pub fn try_decompress(src: &[u8], dst: &mut [u8]) -> Result<usize, LzavError> {
+    if src.is_empty() {
+        return Ok(0);
+    }
+    if dst.is_empty() {
+        return Err(LzavError::InvalidParams);
+    }
+
+    match decompress(src, dst) {
+        // The C decoder only reports the mismatch; the partial decoder says
+        // how much of the output it produced
+        LZAV_E_DSTLEN => Err(LzavError::Corrupt { at: decompress_partial(src, dst).max(0) as usize }),
+        result => LzavError::check(result),
+    }
+}
+
+/// Decompress `src` into `dst` when only an upper bound on the decompressed
+/// length is known, returning the number of bytes written. Unlike
+/// `try_decompress`, a stream that ends before filling `dst` is not an error.
+pub fn decompress_upto(src: &[u8], dst: &mut [u8]) -> Result<usize, LzavError> {
+    match try_decompress(src, dst) {
+        // `lzav_decompress` only reports the short output once the stream
+        // has been decoded to its end
+        Err(LzavError::Corrupt { at }) => Ok(at),
+        result => result,
+    }
+}
+
+/// The C backend as an [`Lzav`](crate::Lzav) implementation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CBackend;
+
+impl crate::Lzav for CBackend {
+    fn compress_default(src: &[u8], dst: &mut [u8]) -> i32 {
+        compress_default(src, dst)
+    }
+
+    fn compress_bound(srcl: i32) -> i32 {
+        compress_bound(srcl)
+    }
+
+    fn decompress(src: &[u8], dst: &mut [u8]) -> i32 {
+        decompress(src, dst)
+    }
+
+    fn decompress_partial(src: &[u8], dst: &mut [u8]) -> i32 {
+        decompress_partial(src, dst)
+    }
+}
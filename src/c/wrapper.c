@@ -5,6 +5,15 @@ int c_lzav_compress_default(const void* src, void* dst, int srcl, int dstl) {
     return lzav_compress_default(src, dst, srcl, dstl);
 }
 
+int c_lzav_compress(const void* src, void* dst, int srcl, int dstl,
+    void* ext_buf, int ext_bufl) {
+    return lzav_compress(src, dst, srcl, dstl, ext_buf, ext_bufl);
+}
+
+int c_lzav_compress_hi(const void* src, void* dst, int srcl, int dstl) {
+    return lzav_compress_hi(src, dst, srcl, dstl);
+}
+
 int c_lzav_compress_bound(int srcl) {
     return lzav_compress_bound(srcl);
 }
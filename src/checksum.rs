@@ -0,0 +1,195 @@
+// CRC-32 (IEEE 802.3, reflected polynomial 0xEDB88320)
+const CRC32_POLY: u32 = 0xEDB8_8320;
+
+const CRC32_TABLE: [u32; 256] = build_table();
+
+const fn build_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ CRC32_POLY } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+// Adler-32 modulus and the longest run before the sums must be reduced
+const ADLER_MOD: u32 = 65521;
+const ADLER_NMAX: usize = 5552;
+
+/// Integrity check algorithms a [`Checksum`] can compute.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ChecksumKind {
+    /// No checksum; always 0.
+    None,
+    /// Add each byte, then rotate left by one bit. Cheap but weak.
+    RotateAdd,
+    /// CRC-32 (IEEE).
+    #[default]
+    Crc32,
+    /// Adler-32, as used by zlib.
+    Adler32,
+}
+
+impl ChecksumKind {
+    /// Checksum of `data` with this algorithm.
+    pub fn compute(self, data: &[u8]) -> u32 {
+        let mut checksum = Checksum::with_kind(self);
+        checksum.update(data);
+        checksum.finalize()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    None,
+    RotateAdd(u32),
+    Crc32(u32), // CRC register, kept inverted between updates
+    Adler32 { a: u32, b: u32 },
+}
+
+/// Incremental checksum for data that arrives in chunks, CRC-32 by default.
+#[derive(Debug, Clone, Copy)]
+pub struct Checksum {
+    state: State,
+}
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Checksum {
+    pub fn new() -> Self {
+        Self::resume(0)
+    }
+
+    /// Start a checksum of the given kind.
+    pub fn with_kind(kind: ChecksumKind) -> Self {
+        let state = match kind {
+            ChecksumKind::None => State::None,
+            ChecksumKind::RotateAdd => State::RotateAdd(0),
+            ChecksumKind::Crc32 => State::Crc32(!0),
+            ChecksumKind::Adler32 => State::Adler32 { a: 1, b: 0 },
+        };
+        Self { state }
+    }
+
+    /// Continue from the CRC-32 of preceding bytes.
+    pub fn resume(crc: u32) -> Self {
+        Self { state: State::Crc32(!crc) }
+    }
+
+    /// The algorithm this checksum computes.
+    pub fn kind(&self) -> ChecksumKind {
+        match self.state {
+            State::None => ChecksumKind::None,
+            State::RotateAdd(_) => ChecksumKind::RotateAdd,
+            State::Crc32(_) => ChecksumKind::Crc32,
+            State::Adler32 { .. } => ChecksumKind::Adler32,
+        }
+    }
+
+    /// Feed the next chunk of data.
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.state {
+            State::None => {}
+            State::RotateAdd(sum) => {
+                for &byte in data {
+                    *sum = sum.wrapping_add(byte as u32).rotate_left(1);
+                }
+            }
+            State::Crc32(crc) => {
+                for &byte in data {
+                    *crc = CRC32_TABLE[((*crc ^ byte as u32) & 0xFF) as usize] ^ (*crc >> 8);
+                }
+            }
+            State::Adler32 { a, b } => {
+                for chunk in data.chunks(ADLER_NMAX) {
+                    for &byte in chunk {
+                        *a += byte as u32;
+                        *b += *a;
+                    }
+                    *a %= ADLER_MOD;
+                    *b %= ADLER_MOD;
+                }
+            }
+        }
+    }
+
+    /// The checksum of all data fed so far.
+    pub fn finalize(self) -> u32 {
+        match self.state {
+            State::None => 0,
+            State::RotateAdd(sum) => sum,
+            State::Crc32(crc) => !crc,
+            State::Adler32 { a, b } => b << 16 | a,
+        }
+    }
+}
+
+/// Compute the Adler-32 checksum of `data`.
+pub fn adler32(data: &[u8]) -> u32 {
+    ChecksumKind::Adler32.compute(data)
+}
+
+/// Compute the CRC-32 (IEEE) checksum of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// Continue a CRC-32 computed over preceding bytes with `data`.
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut checksum = Checksum::resume(crc);
+    checksum.update(data);
+    checksum.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32_update(crc32(b"12345"), b"6789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_checksum_chunks() {
+        let data = b"incremental checksum over arbitrary chunk boundaries";
+        for chunk_len in [1, 3, 7, data.len()] {
+            let mut checksum = Checksum::new();
+            for chunk in data.chunks(chunk_len) {
+                checksum.update(chunk);
+            }
+            assert_eq!(checksum.finalize(), crc32(data));
+        }
+        assert_eq!(Checksum::new().finalize(), 0);
+    }
+
+    #[test]
+    fn test_checksum_kinds() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(ChecksumKind::Crc32.compute(b"123456789"), 0xCBF4_3926);
+        assert_eq!(ChecksumKind::RotateAdd.compute(b"\x01\x01"), 6);
+        assert_eq!(ChecksumKind::None.compute(b"anything"), 0);
+
+        // Adler-32 sums must be reduced across long inputs
+        let long = [0xFFu8; 3 * ADLER_NMAX + 17];
+        let mut split = Checksum::with_kind(ChecksumKind::Adler32);
+        split.update(&long[..1000]);
+        split.update(&long[1000..]);
+        assert_eq!(split.finalize(), adler32(&long));
+        assert_eq!(split.kind(), ChecksumKind::Adler32);
+    }
+}
@@ -1,3 +1,5 @@
+use core::fmt;
+
 // Error codes shared between C and Rust implementations
 pub const LZAV_E_PARAMS: i32 = -1;
 pub const LZAV_E_SRCOOB: i32 = -2;
@@ -20,3 +22,94 @@ pub static LZAV_ERR_CODES: [OnceLock<i32>; 6] = [
     OnceLock::new(), // DSTLEN
     OnceLock::new()  // UNKFMT
 ];
+
+/// Typed counterpart of the `LZAV_E_*` codes, returned by the `Result` API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LzavError {
+    /// Invalid or empty buffers were passed (`LZAV_E_PARAMS`).
+    InvalidParams,
+    /// The source stream ended before a block was complete (`LZAV_E_SRCOOB`).
+    SourceOutOfBounds,
+    /// The destination buffer is too small for the output (`LZAV_E_DSTOOB`).
+    DestOutOfBounds,
+    /// A back-reference points before the start of the output (`LZAV_E_REFOOB`).
+    ReferenceOutOfBounds,
+    /// The decoded length differs from the expected length (`LZAV_E_DSTLEN`).
+    DestLengthMismatch,
+    /// The stream uses an unknown format or token (`LZAV_E_UNKFMT`).
+    UnknownFormat,
+    /// The stream decoded cleanly but stopped short of the expected length;
+    /// `at` is the number of bytes produced, where the output diverged.
+    /// Reported as `LZAV_E_DSTLEN` by the `i32` API.
+    Corrupt { at: usize },
+    /// The stream decoded, but the checksum of the output is `actual` rather
+    /// than the `expected` value given to `decompress_checked` or stored in
+    /// a frame or `CompressedData`. Reported as `LZAV_E_DSTLEN` by the `i32`
+    /// API.
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl LzavError {
+    /// Map a negative `LZAV_E_*` code to its variant, `None` for other values.
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            LZAV_E_PARAMS => Some(Self::InvalidParams),
+            LZAV_E_SRCOOB => Some(Self::SourceOutOfBounds),
+            LZAV_E_DSTOOB => Some(Self::DestOutOfBounds),
+            LZAV_E_REFOOB => Some(Self::ReferenceOutOfBounds),
+            LZAV_E_DSTLEN => Some(Self::DestLengthMismatch),
+            LZAV_E_UNKFMT => Some(Self::UnknownFormat),
+            _ => None,
+        }
+    }
+
+    /// The raw `LZAV_E_*` code used by the `i32` API.
+    pub fn code(self) -> i32 {
+        match self {
+            Self::InvalidParams => LZAV_E_PARAMS,
+            Self::SourceOutOfBounds => LZAV_E_SRCOOB,
+            Self::DestOutOfBounds => LZAV_E_DSTOOB,
+            Self::ReferenceOutOfBounds => LZAV_E_REFOOB,
+            Self::DestLengthMismatch | Self::Corrupt { .. } | Self::ChecksumMismatch { .. } => LZAV_E_DSTLEN,
+            Self::UnknownFormat => LZAV_E_UNKFMT,
+        }
+    }
+
+    /// Convert an `i32` API result into a `Result`, treating negatives as errors.
+    pub fn check(result: i32) -> Result<usize, Self> {
+        if result < 0 {
+            Err(Self::from_code(result).unwrap_or(Self::InvalidParams))
+        } else {
+            Ok(result as usize)
+        }
+    }
+}
+
+impl fmt::Display for LzavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Self::InvalidParams => "invalid parameters",
+            Self::SourceOutOfBounds => "source buffer out of bounds",
+            Self::DestOutOfBounds => "destination buffer out of bounds",
+            Self::ReferenceOutOfBounds => "back-reference out of bounds",
+            Self::DestLengthMismatch => "decompressed length mismatch",
+            Self::UnknownFormat => "unknown stream format",
+            Self::Corrupt { at } => {
+                return write!(f, "corrupt stream, output diverged at byte {}", at);
+            }
+            Self::ChecksumMismatch { expected, actual } => {
+                return write!(f, "checksum mismatch, expected {:#010x} but got {:#010x}", expected, actual);
+            }
+        };
+        f.write_str(msg)
+    }
+}
+
+impl core::error::Error for LzavError {}
+
+#[cfg(feature = "std")]
+impl From<LzavError> for std::io::Error {
+    fn from(err: LzavError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
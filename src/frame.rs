@@ -0,0 +1,581 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::checksum::ChecksumKind;
+use crate::errors::LzavError;
+use crate::large::{compress_large, compress_large_bound, decompress_large};
+use crate::scan::LZAV_MAX_EXPANSION;
+use crate::{compress_append, try_decompress, LZAV_WIN_LEN};
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+
+/// Magic bytes opening every frame written by [`compress_frame`].
+pub const FRAME_MAGIC: [u8; 4] = *b"LZAV";
+/// Frame layout version written by [`compress_frame`]. Version 1 frames,
+/// which lack the body length, are still read.
+pub const FRAME_VERSION: u8 = 2;
+
+// Flag bit: the body uses the `compress_large` block layout
+const FLAG_BLOCKS: u8 = 1;
+// Flag bit 3: an end-of-stream marker without a body
+const FLAG_END: u8 = 1 << 3;
+// Flag bit 4: the body is the original data, as compressing did not shrink it
+const FLAG_STORED: u8 = 1 << 4;
+// Flag bits 1-2: the checksum kind; 0 is CRC-32 so earlier frames still verify
+const FLAG_CHECKSUM_SHIFT: u8 = 1;
+const FLAG_CHECKSUM_MASK: u8 = 3 << FLAG_CHECKSUM_SHIFT;
+
+fn checksum_code(kind: ChecksumKind) -> u8 {
+    match kind {
+        ChecksumKind::Crc32 => 0,
+        ChecksumKind::None => 1,
+        ChecksumKind::RotateAdd => 2,
+        ChecksumKind::Adler32 => 3,
+    }
+}
+
+fn checksum_kind(code: u8) -> ChecksumKind {
+    match code {
+        0 => ChecksumKind::Crc32,
+        1 => ChecksumKind::None,
+        2 => ChecksumKind::RotateAdd,
+        _ => ChecksumKind::Adler32,
+    }
+}
+
+// Magic, version, flags, original length (u64 LE), checksum (u32 LE), and
+// from version 2 the body length (u64 LE)
+const HEADER_LEN_V1: usize = 4 + 1 + 1 + 8 + 4;
+const HEADER_LEN: usize = HEADER_LEN_V1 + 8;
+
+/// End-of-stream marker for concatenated frames: a header with only the end
+/// flag set and every length zero. [`decompress_all`] stops at it.
+pub const FRAME_END: [u8; HEADER_LEN] = *b"LZAV\x02\x08\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+
+/// Compress `src` into a self-describing frame:
+/// `[b"LZAV"][version: u8][flags: u8][original_len: u64le][crc32: u32le]`
+/// `[body_len: u64le]` followed by the compressed body. A body that would
+/// not be smaller than `src` holds `src` itself, flagged as stored. Read it
+/// back with [`decompress_frame`], or with [`decompress_all`] when frames
+/// are concatenated.
+pub fn compress_frame(src: &[u8]) -> Vec<u8> {
+    compress_frame_with(src, ChecksumKind::Crc32)
+}
+
+/// Like [`compress_frame`], with the checksum computed by `kind`. The kind is
+/// recorded in the flags byte; `ChecksumKind::None` skips verification.
+pub fn compress_frame_with(src: &[u8], kind: ChecksumKind) -> Vec<u8> {
+    // Inputs beyond one window are split into independent blocks
+    let blocks = src.len() > LZAV_WIN_LEN;
+    let mut flags = checksum_code(kind) << FLAG_CHECKSUM_SHIFT;
+    if blocks {
+        flags |= FLAG_BLOCKS;
+    }
+
+    let mut frame = Vec::with_capacity(HEADER_LEN);
+    frame.extend_from_slice(&FRAME_MAGIC);
+    frame.push(FRAME_VERSION);
+    frame.push(flags);
+    frame.extend_from_slice(&(src.len() as u64).to_le_bytes());
+    frame.extend_from_slice(&kind.compute(src).to_le_bytes());
+    frame.extend_from_slice(&0u64.to_le_bytes()); // Body length, set below
+
+    if src.is_empty() {
+        return frame;
+    }
+
+    let body_len = if blocks {
+        frame.resize(HEADER_LEN + compress_large_bound(src.len()), 0);
+        compress_large(src, &mut frame[HEADER_LEN..])
+    } else {
+        compress_append(src, &mut frame)
+    }
+    .expect("destination sized by the compress bound");
+
+    frame.truncate(HEADER_LEN + body_len);
+    if body_len >= src.len() {
+        frame.truncate(HEADER_LEN);
+        frame.extend_from_slice(src);
+        frame[5] = flags & !FLAG_BLOCKS | FLAG_STORED;
+    }
+    let body_len = frame.len() - HEADER_LEN;
+    frame[HEADER_LEN_V1..HEADER_LEN].copy_from_slice(&(body_len as u64).to_le_bytes());
+    frame
+}
+
+/// Decompress a frame written by [`compress_frame`] or
+/// [`compress_frame_with`], verifying its length and checksum. `src` must
+/// hold exactly one frame; use [`decompress_all`] for concatenated frames.
+pub fn decompress_frame(src: &[u8]) -> Result<Vec<u8>, LzavError> {
+    match decode_frame(src)? {
+        (Frame::Data(out), consumed) if consumed == src.len() => Ok(out),
+        // Trailing frames and end markers are for `decompress_all`
+        _ => Err(LzavError::InvalidParams),
+    }
+}
+
+/// Decompress back-to-back frames, returning one buffer per frame.
+///
+/// Decoding runs until `src` is exhausted or a [`FRAME_END`] marker, which
+/// must then end the input. A malformed header or a truncated frame is an
+/// error rather than a silent stop.
+pub fn decompress_all(src: &[u8]) -> Result<Vec<Vec<u8>>, LzavError> {
+    let mut frames = Vec::new();
+    let mut rest = src;
+    while !rest.is_empty() {
+        let (frame, consumed) = decode_frame(rest)?;
+        rest = &rest[consumed..];
+        match frame {
+            Frame::Data(out) => frames.push(out),
+            Frame::End if rest.is_empty() => break,
+            Frame::End => return Err(LzavError::UnknownFormat),
+        }
+    }
+    Ok(frames)
+}
+
+/// Compress `src` as a frame (see [`compress_frame`]) and write it to `w`,
+/// returning the number of bytes written.
+#[cfg(feature = "std")]
+pub fn compress_to_writer<W: Write>(src: &[u8], w: &mut W) -> io::Result<usize> {
+    let frame = compress_frame(src);
+    w.write_all(&frame)?;
+    Ok(frame.len())
+}
+
+/// Read one frame from `r`, append its decompressed contents to `out` and
+/// return how many bytes were appended. The reader is left just past the
+/// frame, so back-to-back frames can be read with repeated calls; version 1
+/// frames run to the end of the reader. Decoding errors are `InvalidData`
+/// errors wrapping the [`LzavError`].
+#[cfg(feature = "std")]
+pub fn decompress_from_reader<R: Read>(r: &mut R, out: &mut Vec<u8>) -> io::Result<usize> {
+    let mut frame = vec![0u8; HEADER_LEN_V1];
+    r.read_exact(&mut frame)?;
+    if frame[4] == 1 {
+        r.read_to_end(&mut frame)?;
+    } else {
+        frame.resize(HEADER_LEN, 0);
+        r.read_exact(&mut frame[HEADER_LEN_V1..])?;
+        let body_len = u64::from_le_bytes(frame[HEADER_LEN_V1..].try_into().unwrap());
+
+        // Read through `take` so a hostile length cannot force a huge allocation
+        let read = r.take(body_len).read_to_end(&mut frame)?;
+        if read as u64 != body_len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+    }
+
+    match decode_frame(&frame)? {
+        (Frame::Data(data), _) => {
+            out.extend_from_slice(&data);
+            Ok(data.len())
+        }
+        (Frame::End, _) => Err(LzavError::InvalidParams.into()),
+    }
+}
+
+/// Iterator adapter yielding one frame per chunk of `I`; see
+/// [`FrameIteratorExt::compress_frames`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct CompressFrames<I> {
+    inner: I,
+}
+
+#[cfg(feature = "std")]
+impl<I> Iterator for CompressFrames<I>
+where
+    I: Iterator,
+    I::Item: AsRef<[u8]>,
+{
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|chunk| Ok(compress_frame(chunk.as_ref())))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Iterator adapter decompressing one frame per item of `I`; see
+/// [`FrameIteratorExt::decompress_frames`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct DecompressFrames<I> {
+    inner: I,
+}
+
+#[cfg(feature = "std")]
+impl<I> Iterator for DecompressFrames<I>
+where
+    I: Iterator,
+    I::Item: AsRef<[u8]>,
+{
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|frame| decompress_frame(frame.as_ref()).map_err(io::Error::from))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Lazy frame compression and decompression for iterators of byte chunks.
+///
+/// Items are `io::Result`s so a pipeline can `?` them straight into a
+/// writer; a frame that fails to decode is an `InvalidData` error wrapping
+/// the [`LzavError`], and iteration may continue past it.
+#[cfg(feature = "std")]
+pub trait FrameIteratorExt: Iterator + Sized
+where
+    Self::Item: AsRef<[u8]>,
+{
+    /// Compress each chunk into its own frame, as [`compress_frame`] does.
+    fn compress_frames(self) -> CompressFrames<Self> {
+        CompressFrames { inner: self }
+    }
+
+    /// Decompress each item as a single frame, as [`decompress_frame`] does.
+    fn decompress_frames(self) -> DecompressFrames<Self> {
+        DecompressFrames { inner: self }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<I> FrameIteratorExt for I
+where
+    I: Iterator,
+    I::Item: AsRef<[u8]>,
+{
+}
+
+enum Frame {
+    Data(Vec<u8>),
+    End,
+}
+
+struct Header<'a> {
+    flags: u8,
+    original_len: usize,
+    checksum: u32,
+    body: &'a [u8],
+    consumed: usize,
+}
+
+// Validate the header of the frame at the start of `src`, which must hold
+// its whole body. Data frames must claim a length their body can produce
+fn parse_header(src: &[u8]) -> Result<Header<'_>, LzavError> {
+    let header = src.get(..HEADER_LEN_V1).ok_or(LzavError::SourceOutOfBounds)?;
+    let version = header[4];
+    let flags = header[5];
+    if header[..4] != FRAME_MAGIC
+        || !(1..=FRAME_VERSION).contains(&version)
+        || flags & !(FLAG_BLOCKS | FLAG_CHECKSUM_MASK | FLAG_END | FLAG_STORED) != 0
+        || flags & (FLAG_BLOCKS | FLAG_STORED) == FLAG_BLOCKS | FLAG_STORED
+    {
+        return Err(LzavError::UnknownFormat);
+    }
+
+    let original_len = u64::from_le_bytes(header[6..14].try_into().unwrap());
+    let checksum = u32::from_le_bytes(header[14..18].try_into().unwrap());
+
+    // Version 1 frames have no body length and run to the end of `src`
+    let (body, consumed) = if version == 1 {
+        (&src[HEADER_LEN_V1..], src.len())
+    } else {
+        let header = src.get(..HEADER_LEN).ok_or(LzavError::SourceOutOfBounds)?;
+        let body_len = u64::from_le_bytes(header[HEADER_LEN_V1..].try_into().unwrap());
+        let end = usize::try_from(body_len)
+            .ok()
+            .and_then(|len| len.checked_add(HEADER_LEN))
+            .ok_or(LzavError::SourceOutOfBounds)?;
+        (src.get(HEADER_LEN..end).ok_or(LzavError::SourceOutOfBounds)?, end)
+    };
+
+    if flags & FLAG_END != 0 {
+        if src[..consumed] != FRAME_END {
+            return Err(LzavError::UnknownFormat);
+        }
+        return Ok(Header { flags, original_len: 0, checksum, body, consumed });
+    }
+
+    // Reject impossible lengths before anything is allocated for them; no
+    // body layout yields more than `LZAV_MAX_EXPANSION` bytes per body byte
+    if original_len > body.len() as u64 * LZAV_MAX_EXPANSION as u64 {
+        return Err(LzavError::SourceOutOfBounds);
+    }
+    let original_len = usize::try_from(original_len).map_err(|_| LzavError::DestOutOfBounds)?;
+    Ok(Header { flags, original_len, checksum, body, consumed })
+}
+
+// Decompressed length of the data frame at the start of `src`, from its header
+pub(crate) fn frame_size(src: &[u8]) -> Result<usize, LzavError> {
+    match parse_header(src)? {
+        header if header.flags & FLAG_END != 0 => Err(LzavError::InvalidParams),
+        header => Ok(header.original_len),
+    }
+}
+
+// The body of the data frame at the start of `src` as one raw stream, with
+// its original length and CRC-32. A stored body becomes an `LZAV_STORED`
+// stream; block bodies and other checksum kinds have no such form
+#[cfg(feature = "rust-backend")]
+pub(crate) fn frame_stream(src: &[u8]) -> Result<(Vec<u8>, usize, u32), LzavError> {
+    let Header { flags, original_len, checksum, body, .. } = parse_header(src)?;
+    if flags & FLAG_END != 0 {
+        return Err(LzavError::InvalidParams);
+    }
+    let kind = checksum_kind((flags & FLAG_CHECKSUM_MASK) >> FLAG_CHECKSUM_SHIFT);
+    if flags & FLAG_BLOCKS != 0 || kind != ChecksumKind::Crc32 {
+        return Err(LzavError::UnknownFormat);
+    }
+
+    let stream = if flags & FLAG_STORED != 0 {
+        if body.len() != original_len {
+            return Err(LzavError::Corrupt { at: body.len().min(original_len) });
+        }
+        [&[crate::LZAV_STORED][..], body].concat()
+    } else {
+        body.to_vec()
+    };
+    Ok((stream, original_len, checksum))
+}
+
+// Decode the frame at the start of `src`, returning it and its length
+fn decode_frame(src: &[u8]) -> Result<(Frame, usize), LzavError> {
+    let Header { flags, original_len, checksum, body, consumed } = parse_header(src)?;
+    if flags & FLAG_END != 0 {
+        return Ok((Frame::End, consumed));
+    }
+
+    // Stored bodies skip the decoder, and the zeroing of `out` with it
+    let out = if flags & FLAG_STORED != 0 {
+        if body.len() != original_len {
+            return Err(LzavError::Corrupt { at: body.len().min(original_len) });
+        }
+        body.to_vec()
+    } else {
+        decode_body(flags, original_len, body)?
+    };
+
+    let kind = checksum_kind((flags & FLAG_CHECKSUM_MASK) >> FLAG_CHECKSUM_SHIFT);
+    if kind != ChecksumKind::None {
+        let actual = kind.compute(&out);
+        if actual != checksum {
+            return Err(LzavError::ChecksumMismatch { expected: checksum, actual });
+        }
+    }
+    Ok((Frame::Data(out), consumed))
+}
+
+// Decompress a compressed frame body into a buffer of `original_len` bytes
+fn decode_body(flags: u8, original_len: usize, body: &[u8]) -> Result<Vec<u8>, LzavError> {
+    let mut out = vec![0u8; original_len];
+    let written = if original_len == 0 {
+        0
+    } else if flags & FLAG_BLOCKS != 0 {
+        decompress_large(body, &mut out)?
+    } else {
+        try_decompress(body, &mut out)?
+    };
+
+    if written != out.len() {
+        return Err(LzavError::Corrupt { at: written });
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let original = b"self-describing frame, self-describing frame!".repeat(30);
+        let frame = compress_frame(&original);
+        assert_eq!(&frame[..4], b"LZAV");
+        assert_eq!(&frame[6..14], &(original.len() as u64).to_le_bytes());
+        assert_eq!(decompress_frame(&frame), Ok(original));
+
+        let empty = compress_frame(b"");
+        assert_eq!(empty.len(), HEADER_LEN);
+        assert_eq!(decompress_frame(&empty), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_frame_rejects_bad_input() {
+        let frame = compress_frame(&b"checked frame contents ".repeat(20));
+
+        let mut bad_magic = frame.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(decompress_frame(&bad_magic), Err(LzavError::UnknownFormat));
+
+        let mut bad_crc = frame.clone();
+        bad_crc[14] ^= 1;
+        let actual = u32::from_le_bytes(frame[14..18].try_into().unwrap());
+        assert_eq!(decompress_frame(&bad_crc), Err(LzavError::ChecksumMismatch { expected: actual ^ 1, actual }));
+
+        let mut long = frame.clone();
+        long[6..14].copy_from_slice(&(460u64 + 5).to_le_bytes());
+        assert_eq!(decompress_frame(&long), Err(LzavError::Corrupt { at: 460 }));
+
+        // A header claiming far more output than the body can hold
+        let mut huge = frame.clone();
+        huge[6..14].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(decompress_frame(&huge), Err(LzavError::SourceOutOfBounds));
+
+        assert_eq!(decompress_frame(&frame[..10]), Err(LzavError::SourceOutOfBounds));
+    }
+
+    #[test]
+    fn test_frame_checksum_kinds() {
+        let original = b"checksum kinds recorded in the flags byte ".repeat(20);
+        let kinds = [
+            ChecksumKind::None,
+            ChecksumKind::RotateAdd,
+            ChecksumKind::Crc32,
+            ChecksumKind::Adler32,
+        ];
+
+        for kind in kinds {
+            let frame = compress_frame_with(&original, kind);
+            assert_eq!(decompress_frame(&frame).as_deref(), Ok(&original[..]));
+
+            // The frame ends with literals, so this still decodes, just wrongly
+            let mut corrupt = frame.clone();
+            *corrupt.last_mut().unwrap() ^= 0x20;
+            let result = decompress_frame(&corrupt);
+            if kind == ChecksumKind::None {
+                assert!(result.is_ok_and(|out| out != original));
+            } else {
+                assert!(matches!(result, Err(LzavError::ChecksumMismatch { .. })), "{:?}", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn test_frame_version_1_still_reads() {
+        let original = b"frames written before the body length ".repeat(10);
+        let mut frame = compress_frame(&original);
+        frame.drain(HEADER_LEN_V1..HEADER_LEN);
+        frame[4] = 1;
+        assert_eq!(decompress_frame(&frame), Ok(original));
+    }
+
+    #[test]
+    fn test_decompress_all_concatenated() {
+        let records = [b"first log batch ".repeat(20), Vec::new(), b"second batch ".repeat(40)];
+        let mut stream: Vec<u8> = records.iter().flat_map(|r| compress_frame(r)).collect();
+        assert_eq!(decompress_all(&stream).as_deref(), Ok(&records[..]));
+        assert_eq!(decompress_frame(&stream), Err(LzavError::InvalidParams));
+
+        stream.extend_from_slice(&FRAME_END);
+        assert_eq!(decompress_all(&stream).as_deref(), Ok(&records[..]));
+        assert_eq!(decompress_all(&FRAME_END), Ok(Vec::new()));
+        assert_eq!(decompress_all(b""), Ok(Vec::new()));
+
+        // Data after the end marker, a cut frame and a damaged header all fail
+        let mut trailing = stream.clone();
+        trailing.extend_from_slice(&compress_frame(b"late"));
+        assert_eq!(decompress_all(&trailing), Err(LzavError::UnknownFormat));
+
+        let end = stream.len() - FRAME_END.len();
+        assert_eq!(decompress_all(&stream[..end - 1]), Err(LzavError::SourceOutOfBounds));
+        assert_eq!(decompress_all(&stream[..end + 3]), Err(LzavError::SourceOutOfBounds));
+
+        let mut bad_header = stream.clone();
+        let second = compress_frame(&records[0]).len();
+        bad_header[second + 1] = b'X';
+        assert_eq!(decompress_all(&bad_header), Err(LzavError::UnknownFormat));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_writer_and_reader_wrappers() {
+        let records = [b"written straight to a sink ".repeat(40), Vec::new(), b"second".to_vec()];
+        let mut sink = Vec::new();
+        for record in &records {
+            let written = compress_to_writer(record, &mut sink).unwrap();
+            assert_eq!(written, compress_frame(record).len());
+        }
+
+        let mut reader = io::Cursor::new(&sink);
+        let mut out = b"prefix".to_vec();
+        for record in &records {
+            assert_eq!(decompress_from_reader(&mut reader, &mut out).unwrap(), record.len());
+        }
+        assert_eq!(out, [&b"prefix"[..], &records.concat()].concat());
+        let eof = decompress_from_reader(&mut reader, &mut out).unwrap_err();
+        assert_eq!(eof.kind(), io::ErrorKind::UnexpectedEof);
+
+        // A body length past the end of the input is a clean EOF error
+        let mut cut = compress_frame(&records[0]);
+        cut[HEADER_LEN_V1..HEADER_LEN].copy_from_slice(&u64::MAX.to_le_bytes());
+        let err = decompress_from_reader(&mut &cut[..], &mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut corrupt = compress_frame(&records[0]);
+        corrupt[0] = b'X';
+        let err = decompress_from_reader(&mut &corrupt[..], &mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_frame_iterator_adapters() {
+        let chunks = ["first log line\n".repeat(30), String::new(), "second log line\n".repeat(10)];
+
+        let mut sink = Vec::new();
+        let mut frames = Vec::new();
+        for frame in chunks.iter().compress_frames() {
+            let frame = frame.unwrap();
+            sink.extend_from_slice(&frame);
+            frames.push(frame);
+        }
+        assert_eq!(decompress_all(&sink).unwrap().concat(), chunks.concat().into_bytes());
+
+        let restored: Vec<Vec<u8>> = frames.iter().decompress_frames().collect::<io::Result<_>>().unwrap();
+        assert_eq!(restored, chunks.map(String::into_bytes));
+
+        // A bad frame fails on its own and the rest still decode
+        frames[1][0] = b'X';
+        let results: Vec<_> = frames.into_iter().decompress_frames().collect();
+        assert!(results[0].is_ok() && results[2].is_ok());
+        assert_eq!(results[1].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_incompressible_frame_is_stored() {
+        let mut state = 0x2545_F491u32;
+        let noise: Vec<u8> = (0..4096)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 24) as u8
+            })
+            .collect();
+
+        let frame = compress_frame(&noise);
+        assert_eq!(frame[5] & FLAG_STORED, FLAG_STORED);
+        assert_eq!(&frame[HEADER_LEN..], &noise[..]);
+        assert_eq!(decompress_frame(&frame), Ok(noise.clone()));
+        assert_eq!(compress_frame(&b"compressible text ".repeat(20))[5] & FLAG_STORED, 0);
+
+        // The stored length must match the header, and the checksum still applies
+        let mut short = frame[..frame.len() - 1].to_vec();
+        short[HEADER_LEN_V1..HEADER_LEN].copy_from_slice(&(noise.len() as u64 - 1).to_le_bytes());
+        assert_eq!(decompress_frame(&short), Err(LzavError::Corrupt { at: noise.len() - 1 }));
+        let mut flipped = frame.clone();
+        flipped[HEADER_LEN + 100] ^= 1;
+        assert!(matches!(decompress_frame(&flipped), Err(LzavError::ChecksumMismatch { .. })));
+        let mut both = frame.clone();
+        both[5] |= FLAG_BLOCKS;
+        assert_eq!(decompress_frame(&both), Err(LzavError::UnknownFormat));
+    }
+}
@@ -0,0 +1,281 @@
+#[cfg(feature = "rayon")]
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::errors::LzavError;
+use crate::{compress, compress_bound, try_decompress, LZAV_WIN_LEN};
+#[cfg(feature = "rayon")]
+use crate::{compress_level, CompressLevel};
+
+// Block table entry: original length then compressed length, both u32 LE
+const TABLE_ENTRY_LEN: usize = 8;
+
+/// Buffer size required by [`compress_large`] for `srcl` input bytes.
+pub fn compress_large_bound(srcl: usize) -> usize {
+    let blocks = srcl.div_ceil(LZAV_WIN_LEN);
+    let full = srcl / LZAV_WIN_LEN;
+    let tail = srcl % LZAV_WIN_LEN;
+
+    let mut bound = 4 + blocks * TABLE_ENTRY_LEN;
+    bound += full * compress_bound(LZAV_WIN_LEN as i32) as usize;
+    if tail != 0 {
+        bound += compress_bound(tail as i32) as usize;
+    }
+    bound
+}
+
+/// Compress `src` of any length by splitting it into independent
+/// `LZAV_WIN_LEN` blocks, returning the number of bytes written to `dst`.
+///
+/// The output starts with the block count (u32 LE) and a table of
+/// `[original_len: u32le][compressed_len: u32le]` entries, followed by the
+/// compressed blocks. Read it back with [`decompress_large`]. Empty input
+/// gives an empty table.
+pub fn compress_large(src: &[u8], dst: &mut [u8]) -> Result<usize, LzavError> {
+    compress_blocks(src, dst, LZAV_WIN_LEN)
+}
+
+fn compress_blocks(src: &[u8], dst: &mut [u8], block_len: usize) -> Result<usize, LzavError> {
+    let blocks = src.len().div_ceil(block_len);
+    let table_len = 4 + blocks * TABLE_ENTRY_LEN;
+    if dst.len() < table_len {
+        return Err(LzavError::DestOutOfBounds);
+    }
+
+    let (table, data) = dst.split_at_mut(table_len);
+    table[..4].copy_from_slice(&(blocks as u32).to_le_bytes());
+
+    let entries = table[4..].chunks_exact_mut(TABLE_ENTRY_LEN);
+    let mut op = 0;
+    for (block, entry) in src.chunks(block_len).zip(entries) {
+        let compressed_len = compress(block, &mut data[op..])?;
+        entry[..4].copy_from_slice(&(block.len() as u32).to_le_bytes());
+        entry[4..].copy_from_slice(&(compressed_len as u32).to_le_bytes());
+        op += compressed_len;
+    }
+
+    Ok(table_len + op)
+}
+
+/// Compress `src` of any length into the [`compress_large`] layout at the
+/// given `level`, compressing the `LZAV_WIN_LEN` blocks in parallel on the
+/// rayon thread pool.
+///
+/// Blocks are independent and split at the same boundaries as
+/// [`compress_large`], so the output only differs from it through `level`
+/// and reads back with [`decompress_large`]. Empty input gives an empty table.
+#[cfg(feature = "rayon")]
+pub fn compress_parallel(src: &[u8], level: CompressLevel) -> Vec<u8> {
+    compress_blocks_parallel(src, LZAV_WIN_LEN, level)
+}
+
+#[cfg(feature = "rayon")]
+fn compress_blocks_parallel(src: &[u8], block_len: usize, level: CompressLevel) -> Vec<u8> {
+    use rayon::prelude::*;
+
+    let compressed: Vec<Vec<u8>> = src
+        .par_chunks(block_len)
+        .map(|block| {
+            let mut out = vec![0u8; compress_bound(block.len() as i32) as usize];
+            let len = compress_level(block, &mut out, level)
+                .expect("destination sized by the compress bound");
+            out.truncate(len);
+            out
+        })
+        .collect();
+
+    let table_len = 4 + compressed.len() * TABLE_ENTRY_LEN;
+    let mut dst = Vec::with_capacity(table_len + compressed.iter().map(Vec::len).sum::<usize>());
+    dst.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+    for (block, out) in src.chunks(block_len).zip(&compressed) {
+        dst.extend_from_slice(&(block.len() as u32).to_le_bytes());
+        dst.extend_from_slice(&(out.len() as u32).to_le_bytes());
+    }
+    for out in &compressed {
+        dst.extend_from_slice(out);
+    }
+    dst
+}
+
+/// Decompress the output of [`compress_large`] into `dst`, returning the
+/// total decompressed length. Same as [`decompress_blocks`].
+pub fn decompress_large(src: &[u8], dst: &mut [u8]) -> Result<usize, LzavError> {
+    decompress_blocks(src, dst)
+}
+
+/// Decompress the block table layout written by [`compress_large`] and
+/// [`compress_parallel`] into `out`, returning the total decompressed length.
+///
+/// The whole table is validated and every block given its own slot of `out`
+/// before anything is decoded. Blocks are independent, so with the `rayon`
+/// feature they are decoded in parallel.
+pub fn decompress_blocks(src: &[u8], out: &mut [u8]) -> Result<usize, LzavError> {
+    let count = src.get(..4).ok_or(LzavError::SourceOutOfBounds)?;
+    let blocks = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+
+    let table_len = blocks
+        .checked_mul(TABLE_ENTRY_LEN)
+        .and_then(|len| len.checked_add(4))
+        .ok_or(LzavError::SourceOutOfBounds)?;
+    let table = src.get(4..table_len).ok_or(LzavError::SourceOutOfBounds)?;
+
+    // Each job is a compressed block, its output offset and its output slot
+    let mut jobs = Vec::with_capacity(blocks);
+    let mut ip = table_len;
+    let mut op = 0;
+    let mut rest = out;
+    for entry in table.chunks_exact(TABLE_ENTRY_LEN) {
+        let original_len = u32::from_le_bytes(entry[..4].try_into().unwrap()) as usize;
+        let compressed_len = u32::from_le_bytes(entry[4..].try_into().unwrap()) as usize;
+
+        let block = ip
+            .checked_add(compressed_len)
+            .and_then(|end| src.get(ip..end))
+            .ok_or(LzavError::SourceOutOfBounds)?;
+        if original_len > rest.len() {
+            return Err(LzavError::DestOutOfBounds);
+        }
+        let (slot, tail) = rest.split_at_mut(original_len);
+        rest = tail;
+
+        jobs.push((block, op, slot));
+        ip += compressed_len;
+        op += original_len;
+    }
+
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        jobs.into_par_iter().try_for_each(decode_block)?;
+    }
+    #[cfg(not(feature = "rayon"))]
+    jobs.into_iter().try_for_each(decode_block)?;
+
+    Ok(op)
+}
+
+// Decode one block into its slot, which it must fill exactly
+fn decode_block((block, op, slot): (&[u8], usize, &mut [u8])) -> Result<(), LzavError> {
+    // Positions of corrupt blocks are relative to the whole output
+    let written = try_decompress(block, slot).map_err(|e| match e {
+        LzavError::Corrupt { at } => LzavError::Corrupt { at: op + at },
+        e => e,
+    })?;
+    if written != slot.len() {
+        return Err(LzavError::Corrupt { at: op + written });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_block_table_roundtrip() {
+        let original = b"block table framing, block table framing! ".repeat(100);
+
+        let bound = 4 + 5 * (TABLE_ENTRY_LEN + compress_bound(1000) as usize);
+        let mut compressed = vec![0u8; bound];
+        let compressed_len = compress_blocks(&original, &mut compressed, 1000).unwrap();
+        assert_eq!(&compressed[..4], &5u32.to_le_bytes());
+
+        let mut decompressed = vec![0u8; original.len()];
+        let result = decompress_large(&compressed[..compressed_len], &mut decompressed);
+        assert_eq!(result, Ok(original.len()));
+        assert_eq!(decompressed, original);
+
+        assert_eq!(
+            decompress_large(&compressed[..compressed_len - 1], &mut decompressed),
+            Err(LzavError::SourceOutOfBounds)
+        );
+        assert_eq!(
+            decompress_large(&compressed[..compressed_len], &mut decompressed[..100]),
+            Err(LzavError::DestOutOfBounds)
+        );
+
+        // The third block claims 8 more bytes than it holds
+        let mut long = compressed[..compressed_len].to_vec();
+        let entry = 4 + 2 * TABLE_ENTRY_LEN;
+        long[entry..entry + 4].copy_from_slice(&1008u32.to_le_bytes());
+        let mut decompressed = vec![0u8; original.len() + 8];
+        assert_eq!(decompress_large(&long, &mut decompressed), Err(LzavError::Corrupt { at: 3000 }));
+    }
+
+    #[test]
+    fn test_decompress_blocks_slots() {
+        let original: Vec<u8> = (0..20_000u32).flat_map(|i| (i / 7).to_le_bytes()).collect();
+
+        let blocks = original.len().div_ceil(4096);
+        let mut compressed = vec![0u8; 4 + blocks * (TABLE_ENTRY_LEN + compress_bound(4096) as usize)];
+        let compressed_len = compress_blocks(&original, &mut compressed, 4096).unwrap();
+        let compressed = &compressed[..compressed_len];
+
+        // Extra room in `out` is left untouched
+        let mut out = vec![0xEE; original.len() + 16];
+        assert_eq!(decompress_blocks(compressed, &mut out), Ok(original.len()));
+        assert_eq!(out[..original.len()], original);
+        assert!(out[original.len()..].iter().all(|&b| b == 0xEE));
+
+        // A bad table is rejected before any block is decoded
+        let mut out = vec![0u8; original.len()];
+        assert_eq!(
+            decompress_blocks(&compressed[..compressed_len - 1], &mut out),
+            Err(LzavError::SourceOutOfBounds)
+        );
+        assert!(out.iter().all(|&b| b == 0));
+
+        // A compressed length that would wrap `usize` on 32-bit targets
+        let mut hostile = compressed.to_vec();
+        hostile[4 + 4..4 + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decompress_blocks(&hostile, &mut out), Err(LzavError::SourceOutOfBounds));
+
+        // A corrupt block reports its position in the whole output
+        let mut corrupt = compressed.to_vec();
+        let entry = 4 + 3 * TABLE_ENTRY_LEN;
+        corrupt[entry..entry + 4].copy_from_slice(&4100u32.to_le_bytes());
+        let mut out = vec![0u8; original.len() + 4];
+        assert_eq!(decompress_blocks(&corrupt, &mut out), Err(LzavError::Corrupt { at: 4 * 4096 }));
+    }
+
+    #[test]
+    fn test_large_input_over_window() {
+        let original: Vec<u8> = b"larger than one window "
+            .iter()
+            .cycle()
+            .take(LZAV_WIN_LEN + 4096)
+            .copied()
+            .collect();
+
+        let mut compressed = vec![0u8; compress_large_bound(original.len())];
+        let compressed_len = compress_large(&original, &mut compressed).unwrap();
+        assert_eq!(&compressed[..4], &2u32.to_le_bytes());
+
+        let mut decompressed = vec![0u8; original.len()];
+        let result = decompress_large(&compressed[..compressed_len], &mut decompressed);
+        assert_eq!(result, Ok(original.len()));
+        assert!(decompressed == original);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let original = b"blocks compressed on the thread pool ".repeat(1000);
+
+        let parallel = compress_blocks_parallel(&original, 1000, CompressLevel::Default);
+        let blocks = original.len().div_ceil(1000);
+        let mut sequential = vec![0u8; 4 + blocks * (TABLE_ENTRY_LEN + compress_bound(1000) as usize)];
+        let sequential_len = compress_blocks(&original, &mut sequential, 1000).unwrap();
+        assert_eq!(parallel, &sequential[..sequential_len]);
+
+        for level in [CompressLevel::Fast, CompressLevel::Max] {
+            let parallel = compress_blocks_parallel(&original, 1000, level);
+            let mut decompressed = vec![0u8; original.len()];
+            assert_eq!(decompress_large(&parallel, &mut decompressed), Ok(original.len()));
+            assert_eq!(decompressed, original);
+        }
+
+        let empty = compress_parallel(b"", CompressLevel::Default);
+        assert_eq!(decompress_large(&empty, &mut []), Ok(0));
+    }
+}
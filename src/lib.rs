@@ -1,9 +1,90 @@
 #![allow(non_upper_case_globals)]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Compressor diagnostics, logged under the `rlzav` target with the `log`
+// feature. Without it the arguments are never evaluated
+#[cfg(feature = "log")]
+macro_rules! lzav_debug {
+    ($($arg:tt)*) => { log::debug!(target: "rlzav", $($arg)*) };
+}
+#[cfg(not(feature = "log"))]
+macro_rules! lzav_debug {
+    ($($arg:tt)*) => {};
+}
+#[cfg(feature = "log")]
+#[allow(unused_macros)]
+macro_rules! lzav_trace {
+    ($($arg:tt)*) => { log::trace!(target: "rlzav", $($arg)*) };
+}
+#[cfg(not(feature = "log"))]
+#[allow(unused_macros)]
+macro_rules! lzav_trace {
+    ($($arg:tt)*) => {};
+}
 
 // Shared modules between implementations
+#[cfg(feature = "std")]
+pub mod archive;
+pub mod checksum;
 pub mod errors;
+pub mod frame;
+pub mod large;
+mod scan;
+pub mod sink;
+#[cfg(feature = "std")]
+pub mod stream;
+pub mod utils;
+
+pub use crate::errors::LzavError;
+pub use crate::sink::Sink;
+pub use crate::utils::AlignedBuffer;
+pub use crate::frame::{compress_frame, compress_frame_with, decompress_all, decompress_frame};
+#[cfg(feature = "std")]
+pub use crate::frame::{
+    compress_to_writer, decompress_from_reader, CompressFrames, DecompressFrames, FrameIteratorExt,
+};
+pub use crate::large::{compress_large, compress_large_bound, decompress_blocks, decompress_large};
+#[cfg(feature = "rayon")]
+pub use crate::large::compress_parallel;
+#[cfg(feature = "std")]
+pub use crate::archive::{LzavArchive, LzavArchiveWriter};
+#[cfg(feature = "std")]
+pub use crate::stream::{LzavDecoder, LzavEncoder, LzavReadDecoder};
+
+/// Largest block a single LZAV stream can reference back into (8 MiB).
+pub const LZAV_WIN_LEN: usize = 1 << 23;
+
+/// API version of the LZAV library this crate follows (`LZAV_API_VER`).
+pub const LZAV_API_VER: u32 = 0x106;
+/// LZAV source version this crate follows (`LZAV_VER_STR`).
+pub const LZAV_VER_STR: &str = "4.5";
+/// Stream format written by both backends' compressors.
+pub const LZAV_FMT_CUR: u8 = 2;
+/// Oldest stream format both backends' decompressors still read.
+pub const LZAV_FMT_MIN: u8 = 1;
+
+/// LZAV API version, `LZAV_API_VER`.
+pub const fn api_version() -> u32 {
+    LZAV_API_VER
+}
+
+/// LZAV source version string, `LZAV_VER_STR`.
+pub const fn version_str() -> &'static str {
+    LZAV_VER_STR
+}
+
+/// Stream formats `decompress` reads. The format is the high nibble of a
+/// stream's first byte.
+pub const fn supported_formats() -> core::ops::RangeInclusive<u8> {
+    LZAV_FMT_MIN..=LZAV_FMT_CUR
+}
 
 // Implementation-specific modules
 #[cfg(feature = "c-backend")]
@@ -11,15 +92,16 @@ pub mod c;
 #[cfg(feature = "rust-backend")]
 pub mod rust;
 
-// Re-export the active implementation
+// Re-export the active implementation. With both backends enabled the C
+// backend provides the top-level API and the Rust one stays under `rust`
 #[cfg(feature = "c-backend")]
 pub use crate::c::*;
 
-#[cfg(feature = "rust-backend")]
+#[cfg(all(feature = "rust-backend", not(feature = "c-backend")))]
 pub use crate::rust::*;
 
 // Re-export compression functions
-#[cfg(feature = "rust-backend")]
+#[cfg(all(feature = "rust-backend", not(feature = "c-backend")))]
 pub use crate::rust::{
     compress_default,
     compress_bound,
@@ -29,13 +111,192 @@ pub use crate::rust::{
 pub use crate::c::compress_bound;
 
 // Re-export decompression function
-#[cfg(feature = "rust-backend")]
+#[cfg(all(feature = "rust-backend", not(feature = "c-backend")))]
 pub use crate::rust::decompress;
 
 #[cfg(feature = "c-backend")]
 pub use crate::c::decompress;
 
+/// Buffer size required to compress `srcl` bytes, or `None` when it does
+/// not fit in `usize`. Both backends' `compress_bound` delegate here.
+///
+/// Being `const`, it can size stack buffers for inputs of a known maximum:
+/// `[0u8; compress_bound_usize(N).unwrap()]`.
+pub const fn compress_bound_usize(srcl: usize) -> Option<usize> {
+    if srcl == 0 {
+        return Some(16);
+    }
+
+    // Worst case of the encoders: a reference of `rc >= 6` bytes costs at
+    // most `rc - 2` bytes, and a literal run costs one header byte below 16
+    // bytes, two below 144 and three below 16400. So a literal run plus its
+    // reference only expands when the run is at least 144 bytes, by at most
+    // one byte per 150 input bytes. The prefix byte and the final literal
+    // header add at most 7 bytes. This gives `srcl + srcl / 150 + 7`, which
+    // the formula below (about `srcl + srcl / 150 + 16`, the same as
+    // `lzav_compress_bound`) covers.
+    let k = 16 + 127 + 1;
+    let l2 = srcl / (k + 6);
+    let extra = (srcl - l2 * 6).div_ceil(k) * 2 - l2 + 16;
+    srcl.checked_add(extra)
+}
+
+/// First byte of a stream [`compress_or_store`] kept uncompressed. LZAV
+/// numbers its formats from 1, so no compressed stream starts with it.
+pub const LZAV_STORED: u8 = 0;
+
+/// Compress `src` into `dst`, or copy it after an [`LZAV_STORED`] byte when
+/// compressing would not make it smaller. Returns whether `dst` holds a
+/// compressed stream, and its length. Both forms decompress with
+/// `decompress`. `dst` needs `compress_bound(src.len())` bytes either way.
+pub fn compress_or_store(src: &[u8], dst: &mut [u8]) -> Result<(bool, usize), LzavError> {
+    let len = compress(src, dst)?;
+    if len < src.len() || src.is_empty() {
+        return Ok((true, len));
+    }
+
+    dst[0] = LZAV_STORED;
+    dst[1..=src.len()].copy_from_slice(src);
+    Ok((false, src.len() + 1))
+}
+
+// The payload of a stream written as stored by `compress_or_store`
+pub(crate) fn stored_payload(src: &[u8]) -> Option<&[u8]> {
+    match src {
+        [LZAV_STORED, payload @ ..] if !payload.is_empty() => Some(payload),
+        _ => None,
+    }
+}
+
+/// Compress `src` into a new `Vec` sized to the compressed stream.
+pub fn compress_to_vec(src: &[u8]) -> Result<Vec<u8>, LzavError> {
+    let mut dst = Vec::new();
+    compress_append(src, &mut dst)?;
+    Ok(dst)
+}
+
+/// Compress `src` onto the end of `out`, keeping what it already holds, and
+/// return the number of bytes appended. On error `out` is left unchanged.
+pub fn compress_append(src: &[u8], out: &mut Vec<u8>) -> Result<usize, LzavError> {
+    let start = out.len();
+    out.resize(start + compress_bound(src.len() as i32) as usize, 0);
+    match compress(src, &mut out[start..]) {
+        Ok(len) => {
+            out.truncate(start + len);
+            Ok(len)
+        }
+        Err(e) => {
+            out.truncate(start);
+            Err(e)
+        }
+    }
+}
+
+/// Size of the buffer `src` decompresses into, without decompressing it:
+/// the original length from the header of a frame (see [`compress_frame`]),
+/// or the sum of the block lengths of a raw stream. Headers are validated,
+/// and a truncated input is an error or counts only the blocks it holds, so
+/// the result is never more than `src` can produce.
+pub fn decompressed_size(src: &[u8]) -> Result<usize, LzavError> {
+    if src.starts_with(&frame::FRAME_MAGIC) {
+        frame::frame_size(src)
+    } else {
+        scan::stream_size(src)
+    }
+}
+
+/// Compress the concatenation of `bufs`, such as the `IoSlice`s of a
+/// vectored read, into `dst`, returning the number of bytes written. The
+/// output is the same as compressing the joined input.
+///
+/// The match finder needs one contiguous window, so the buffers are copied
+/// into a scratch buffer first, unless there is only one. With `std` the
+/// scratch buffer is reused per thread and keeps its capacity.
+pub fn compress_vectored<B: core::ops::Deref<Target = [u8]>>(bufs: &[B], dst: &mut [u8]) -> Result<usize, LzavError> {
+    let mut nonempty = bufs.iter().filter(|buf| !buf.is_empty());
+    match (nonempty.next(), nonempty.next()) {
+        (None, _) => return compress(&[], dst),
+        (Some(only), None) => return compress(only, dst),
+        _ => {}
+    }
+
+    let mut gather = |joined: &mut Vec<u8>| {
+        joined.clear();
+        for buf in bufs {
+            joined.extend_from_slice(buf);
+        }
+        compress(joined, dst)
+    };
+
+    #[cfg(feature = "std")]
+    {
+        std::thread_local! {
+            static GATHERED: core::cell::RefCell<Vec<u8>> = const { core::cell::RefCell::new(Vec::new()) };
+        }
+        GATHERED.with(|joined| gather(&mut joined.borrow_mut()))
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        gather(&mut Vec::new())
+    }
+}
+
+/// Decompress `src` into a new `Vec` of `original_len` bytes.
+pub fn decompress_to_vec(src: &[u8], original_len: usize) -> Result<Vec<u8>, LzavError> {
+    let mut dst = vec![0u8; original_len];
+    let len = try_decompress(src, &mut dst)?;
+    dst.truncate(len);
+    Ok(dst)
+}
+
+/// Decompress `src` into `dst` like `try_decompress`, then check the CRC-32
+/// of the output against `expected`, a checksum kept outside the stream such
+/// as in a manifest. A mismatch is reported as `ChecksumMismatch`.
+pub fn decompress_checked(src: &[u8], dst: &mut [u8], expected: u32) -> Result<usize, LzavError> {
+    let len = try_decompress(src, dst)?;
+    let actual = checksum::crc32(&dst[..len]);
+    if actual != expected {
+        return Err(LzavError::ChecksumMismatch { expected, actual });
+    }
+    Ok(len)
+}
+
+/// Compress `src` into a new [`Bytes`](bytes::Bytes), writing straight into
+/// its buffer.
+#[cfg(feature = "bytes")]
+pub fn compress_bytes(src: &bytes::Bytes) -> Result<bytes::Bytes, LzavError> {
+    let mut dst = bytes::BytesMut::zeroed(compress_bound(src.len() as i32) as usize);
+    let len = compress(src, &mut dst)?;
+    dst.truncate(len);
+    Ok(dst.freeze())
+}
+
+/// Decompress `src` into a new [`Bytes`](bytes::Bytes) of `original_len`
+/// bytes, writing straight into its buffer.
+#[cfg(feature = "bytes")]
+pub fn decompress_bytes(src: &bytes::Bytes, original_len: usize) -> Result<bytes::Bytes, LzavError> {
+    let mut dst = bytes::BytesMut::zeroed(original_len);
+    let len = try_decompress(src, &mut dst)?;
+    dst.truncate(len);
+    Ok(dst.freeze())
+}
+
+/// Match search effort for `compress_level`. Every level writes the same
+/// stream format, so the output decompresses identically. The C backend has
+/// no fast mode, so `Fast` writes the same stream as `Default` there.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CompressLevel {
+    /// Shallow match search with aggressive skipping of incompressible data.
+    Fast,
+    /// The effort used by `compress`.
+    #[default]
+    Default,
+    /// Deep match search without skipping, for better ratio at lower speed.
+    Max,
+}
+
 // Common interface that both implementations must provide
+/// Backend-agnostic LZAV interface, implemented by `RustBackend` and `CBackend`.
 pub trait Lzav {
     fn compress_default(src: &[u8], dst: &mut [u8]) -> i32;
     fn compress_bound(srcl: i32) -> i32;
@@ -95,6 +356,312 @@ mod tests {
         if original.len() > 16 {
             println!("...");
         }
+
+        // The Vec convenience API produces the same stream
+        let compressed_vec = compress_to_vec(original).unwrap();
+        assert_eq!(&compressed_vec[..], &compressed[..compressed_len as usize]);
+        assert_eq!(decompress_to_vec(&compressed_vec, original.len()).unwrap(), original);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_bytes_roundtrip() {
+        let original = bytes::Bytes::from(b"bytes in, bytes out; bytes in, bytes out".repeat(20));
+        let compressed = compress_bytes(&original).unwrap();
+        assert_eq!(&compressed[..], &compress_to_vec(&original).unwrap()[..]);
+        assert_eq!(decompress_bytes(&compressed, original.len()).unwrap(), original);
+
+        assert_eq!(compress_bytes(&bytes::Bytes::new()), Ok(bytes::Bytes::new()));
+        let mut corrupt = bytes::BytesMut::from(&compressed[..]);
+        corrupt[0] = 0x76;
+        assert!(decompress_bytes(&corrupt.freeze(), original.len()).is_err());
+    }
+
+    #[test]
+    fn test_decompressed_size() {
+        let data = b"sized before decoding, sized before decoding ".repeat(50);
+        let raw = compress_to_vec(&data).unwrap();
+        let frame = compress_frame(&data);
+        assert_eq!(decompressed_size(&raw), Ok(data.len()));
+        assert_eq!(decompressed_size(&frame), Ok(data.len()));
+        assert_eq!(decompressed_size(&compress_to_vec(b"tiny").unwrap()), Ok(4));
+        assert_eq!(decompressed_size(&compress_frame(b"")), Ok(0));
+
+        // Truncation never over-reports
+        for cut in 1..raw.len() {
+            if let Ok(size) = decompressed_size(&raw[..cut]) {
+                assert!(size <= data.len(), "{cut}: {size}");
+            }
+        }
+        for cut in 1..frame.len() {
+            assert!(decompressed_size(&frame[..cut]).is_err());
+        }
+
+        let mut bad_version = frame.clone();
+        bad_version[4] = 9;
+        assert_eq!(decompressed_size(&bad_version), Err(LzavError::UnknownFormat));
+        assert_eq!(decompressed_size(&frame::FRAME_END), Err(LzavError::InvalidParams));
+        assert_eq!(decompressed_size(b""), Ok(0));
+    }
+
+    #[test]
+    fn test_empty_input_roundtrips() {
+        let mut dst = [0u8; 16];
+        assert_eq!(compress(b"", &mut dst), Ok(0));
+        assert_eq!(compress(b"", &mut []), Ok(0));
+        assert_eq!(compress_default(b"", &mut dst), 0);
+        assert_eq!(compress_level(b"", &mut dst, CompressLevel::Max), Ok(0));
+        assert_eq!(compress_with_scratch(b"", &mut dst, &mut [0u8; 64]), 0);
+        assert_eq!(compress_to_vec(b""), Ok(Vec::new()));
+
+        assert_eq!(try_decompress(b"", &mut dst), Ok(0));
+        assert_eq!(decompress(b"", &mut dst), 0);
+        assert_eq!(decompress_partial(b"", &mut dst), 0);
+        assert_eq!(decompress_upto(b"", &mut dst), Ok(0));
+        assert_eq!(decompress_to_vec(b"", 0), Ok(Vec::new()));
+        assert_eq!(decompressed_size(b""), Ok(0));
+
+        let mut large = [0u8; 4];
+        assert_eq!(compress_large(b"", &mut large), Ok(4));
+        assert_eq!(decompress_large(&large, &mut []), Ok(0));
+        assert_eq!(decompress_frame(&compress_frame(b"")), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_compress_into_sinks() {
+        let original = b"one encoder, three kinds of sink, one encoder, three kinds of sink";
+        let expected = compress_to_vec(original).unwrap();
+
+        let mut vec = b"prefix".to_vec();
+        assert_eq!(compress_into(original, &mut vec), Ok(expected.len()));
+        assert_eq!(&vec[6..], &expected[..]);
+
+        let mut buf = [0u8; 256];
+        let mut rest = &mut buf[..];
+        assert_eq!(compress_into(original, &mut rest), Ok(expected.len()));
+        assert_eq!(rest.len(), 256 - expected.len());
+        assert_eq!(&buf[..expected.len()], &expected[..]);
+        let mut small = [0u8; 8];
+        assert_eq!(compress_into(original, &mut small[..]), Err(LzavError::DestOutOfBounds));
+        assert_eq!(small, [0u8; 8]);
+
+        #[cfg(feature = "std")]
+        {
+            let mut written = Vec::new();
+            let writer: &mut dyn std::io::Write = &mut written;
+            assert_eq!(compress_into(original, writer).unwrap(), expected.len());
+            assert_eq!(written, expected);
+        }
+        assert_eq!(compress_into(b"", &mut vec), Ok(0));
+    }
+
+    #[test]
+    fn test_compress_vectored_matches_concatenated() {
+        let data = b"scatter gather input, split across reads; scatter gather input again. ".repeat(40);
+        let expected = compress_to_vec(&data).unwrap();
+        let mut dst = vec![0u8; compress_bound(data.len() as i32) as usize];
+
+        for cuts in [&[][..], &[1][..], &[100, 100, 101, 2000], &[7, 13, 64, 65, 1000, 2799]] {
+            let mut bufs = Vec::new();
+            let mut start = 0;
+            for &cut in cuts.iter().chain([&data.len()]) {
+                bufs.push(&data[start..cut]);
+                start = cut;
+            }
+            let len = compress_vectored(&bufs, &mut dst).unwrap();
+            assert_eq!(&dst[..len], &expected[..], "cut at {cuts:?}");
+        }
+
+        #[cfg(feature = "std")]
+        {
+            let slices = [std::io::IoSlice::new(&data[..500]), std::io::IoSlice::new(&data[500..])];
+            let len = compress_vectored(&slices, &mut dst).unwrap();
+            assert_eq!(&dst[..len], &expected[..]);
+        }
+        assert_eq!(compress_vectored::<&[u8]>(&[], &mut dst), Ok(0));
+    }
+
+    // Malformed streams kept as regression inputs, in the decompress fuzz
+    // target's layout: a u16 LE destination size, then the stream
+    const MALFORMED_STREAMS: [(&str, &[u8]); 5] = [
+        ("bad_format_nibble", include_bytes!("../fuzz/regressions/decompress/bad_format_nibble.bin")),
+        ("truncated_reference", include_bytes!("../fuzz/regressions/decompress/truncated_reference.bin")),
+        ("distance_past_output", include_bytes!("../fuzz/regressions/decompress/distance_past_output.bin")),
+        ("literal_past_source", include_bytes!("../fuzz/regressions/decompress/literal_past_source.bin")),
+        ("literal_len_overflow", include_bytes!("../fuzz/regressions/decompress/literal_len_overflow.bin")),
+    ];
+
+    #[test]
+    fn test_malformed_stream_regressions() {
+        for (name, input) in MALFORMED_STREAMS {
+            let (len, src) = input.split_first_chunk::<2>().unwrap();
+            let mut dst = vec![0u8; u16::from_le_bytes(*len) as usize];
+
+            assert!(try_decompress(src, &mut dst).is_err(), "{name} decoded");
+            assert!(decompress(src, &mut dst) < 0, "{name} decoded");
+            let partial = decompress_partial(src, &mut dst);
+            assert!(partial <= dst.len() as i32, "{name} overran dst");
+        }
+    }
+
+    #[test]
+    fn test_try_decompress_fixed_buffer() {
+        let original = b"arena backed output, arena backed output, arena backed output";
+        let compressed = compress_to_vec(original).unwrap();
+
+        let mut arena = [0u8; 256];
+        let len = try_decompress(&compressed, &mut arena[..original.len()]).unwrap();
+        assert_eq!(&arena[..len], original);
+        assert!(arena[len..].iter().all(|&b| b == 0));
+
+        let mut small = [0u8; 16];
+        assert!(try_decompress(&compressed, &mut small).is_err());
+    }
+
+    #[test]
+    fn test_decompress_checked() {
+        let original = b"checked against a manifest, checked against a manifest".repeat(10);
+        let compressed = compress_to_vec(&original).unwrap();
+        let expected = checksum::crc32(&original);
+        let mut dst = vec![0u8; original.len()];
+
+        assert_eq!(decompress_checked(&compressed, &mut dst, expected), Ok(original.len()));
+        assert_eq!(dst, original);
+        assert_eq!(
+            decompress_checked(&compressed, &mut dst, expected ^ 1),
+            Err(LzavError::ChecksumMismatch { expected: expected ^ 1, actual: expected })
+        );
+        assert_eq!(LzavError::ChecksumMismatch { expected: 0, actual: 1 }.code(), errors::LZAV_E_DSTLEN);
+
+        // Decoding errors come first; there is no output to checksum
+        assert_eq!(decompress_checked(&compressed, &mut dst[..10], expected).unwrap_err().code(), errors::LZAV_E_DSTOOB);
+    }
+
+    #[test]
+    fn test_decompress_upto() {
+        let data = b"upper bound only, upper bound only".repeat(30);
+        let compressed = compress_to_vec(&data).unwrap();
+
+        for room in [data.len(), data.len() + 1, 4 * data.len()] {
+            let mut out = vec![0u8; room];
+            assert_eq!(decompress_upto(&compressed, &mut out), Ok(data.len()));
+            assert_eq!(out[..data.len()], data);
+        }
+
+        // Only `decompress_upto` accepts room to spare
+        let mut out = vec![0u8; data.len() + 100];
+        let short = Err(LzavError::Corrupt { at: data.len() });
+        #[cfg(feature = "c-backend")]
+        assert_eq!(c::try_decompress(&compressed, &mut out), short);
+        #[cfg(feature = "rust-backend")]
+        assert_eq!(rust::try_decompress(&compressed, &mut out), short);
+
+        let mut out = vec![0u8; data.len() - 1];
+        assert_eq!(decompress_upto(&compressed, &mut out), Err(LzavError::DestOutOfBounds));
+        let mut out = vec![0u8; 4 * data.len()];
+        assert_eq!(decompress_upto(&[0x76, 0, 0, 0, 0, 0, 0, 0], &mut out), Err(LzavError::UnknownFormat));
+        assert_eq!(decompress_upto(&compressed, &mut []), Err(LzavError::InvalidParams));
+    }
+
+    #[test]
+    fn test_compress_append() {
+        let first = b"first block, first block, first block".repeat(8);
+        let second = b"second block".repeat(30);
+
+        let mut out = b"container header".to_vec();
+        let first_len = compress_append(&first, &mut out).unwrap();
+        let second_len = compress_append(&second, &mut out).unwrap();
+        assert_eq!(out.len(), 16 + first_len + second_len);
+        assert_eq!(&out[..16], b"container header");
+
+        let (first_stream, second_stream) = out[16..].split_at(first_len);
+        assert_eq!(first_stream, &compress_to_vec(&first).unwrap()[..]);
+        assert_eq!(decompress_to_vec(first_stream, first.len()).unwrap(), first);
+        assert_eq!(decompress_to_vec(second_stream, second.len()).unwrap(), second);
+
+        assert_eq!(compress_append(b"", &mut out), Ok(0));
+        assert_eq!(out.len(), 16 + first_len + second_len);
+    }
+
+    #[test]
+    fn test_truncated_streams_never_panic() {
+        let mut rng = rand::thread_rng();
+        let random: Vec<u8> = (0..300).map(|_| rng.gen()).collect();
+        // Long literal runs, short references and maximal-length references,
+        // so truncation lands inside every kind of length field
+        let data = [
+            random.clone(),
+            b"header: value\n".repeat(12),
+            vec![0; 1500],
+            random,
+            b"tail".to_vec(),
+        ]
+        .concat();
+        let compressed = compress_to_vec(&data).unwrap();
+
+        for len in 1..=compressed.len() {
+            let stream = &compressed[..len];
+            let mut out = vec![0u8; data.len()];
+            if let Ok(n) = try_decompress(stream, &mut out) {
+                assert!(n <= data.len());
+                assert_eq!(out[..n], data[..n], "truncated at {len}");
+            }
+
+            let mut out = vec![0u8; data.len()];
+            let n = decompress_partial(stream, &mut out);
+            if n > 0 {
+                assert_eq!(out[..n as usize], data[..n as usize], "truncated at {len}");
+            }
+        }
+        assert_eq!(decompress_to_vec(&compressed, data.len()).unwrap(), data);
+    }
+
+    #[test]
+    fn test_versions_match_lzav_h() {
+        let header = include_str!("../lzav.h");
+        let define = |name: &str| {
+            let prefix = format!("#define {name} ");
+            let line = header.lines().find(|l| l.trim_start().starts_with(&prefix)).unwrap();
+            line.split_whitespace().nth(2).unwrap().to_owned()
+        };
+
+        assert_eq!(format!("{:#x}", api_version()), define("LZAV_API_VER"));
+        assert_eq!(format!("\"{}\"", version_str()), define("LZAV_VER_STR"));
+        let (min, cur) = (define("LZAV_FMT_MIN").parse().unwrap(), define("LZAV_FMT_CUR").parse().unwrap());
+        assert_eq!(supported_formats(), min..=cur);
+
+        let compressed = compress_to_vec(&b"which format? ".repeat(10)).unwrap();
+        assert_eq!(compressed[0] >> 4, LZAV_FMT_CUR);
+        assert!(supported_formats().contains(&(compressed[0] >> 4)));
+    }
+
+    #[test]
+    fn test_compress_or_store() {
+        let mut rng = rand::thread_rng();
+        let random: Vec<u8> = (0..4096).map(|_| rng.gen()).collect();
+        let text = b"compressible text, compressible text, compressible text".repeat(20);
+
+        for (data, expect_compressed) in [(&random, false), (&text, true)] {
+            let mut dst = vec![0u8; compress_bound(data.len() as i32) as usize];
+            let (compressed, len) = compress_or_store(data, &mut dst).unwrap();
+            assert_eq!(compressed, expect_compressed);
+            assert!(len <= data.len() + 1);
+            assert_eq!(dst[0] == LZAV_STORED, !compressed);
+
+            let stream = &dst[..len];
+            assert_eq!(decompress_to_vec(stream, data.len()).unwrap(), *data);
+            let mut out = vec![0u8; data.len()];
+            assert_eq!(decompress(stream, &mut out), data.len() as i32);
+            assert_eq!(out, *data);
+
+            let mut short = vec![0u8; data.len() / 2];
+            assert_eq!(decompress_partial(stream, &mut short), short.len() as i32);
+            assert_eq!(short, data[..short.len()]);
+            assert!(try_decompress(stream, &mut short).is_err());
+        }
+
+        let mut dst = [0u8; 8];
+        assert_eq!(compress_or_store(b"", &mut dst), Ok((true, 0)));
     }
 
     #[test]
@@ -103,6 +670,87 @@ mod tests {
         verify_roundtrip(original);
     }
 
+    fn roundtrip<L: Lzav>() {
+        // Empty input is an empty stream, in both directions
+        assert_eq!(L::compress_default(b"", &mut [0u8; 16]), 0);
+        assert_eq!(L::decompress(b"", &mut []), 0);
+        assert_eq!(L::decompress_partial(b"", &mut [0u8; 4]), 0);
+
+        let original = b"generic backend roundtrip, generic backend roundtrip";
+        let mut compressed = vec![0u8; L::compress_bound(original.len() as i32) as usize];
+        let compressed_len = L::compress_default(original, &mut compressed);
+        assert!(compressed_len > 0);
+
+        let mut decompressed = vec![0u8; original.len()];
+        let compressed = &compressed[..compressed_len as usize];
+        assert_eq!(L::decompress(compressed, &mut decompressed), original.len() as i32);
+        assert_eq!(&decompressed[..], &original[..]);
+
+        let mut prefix = [0u8; 16];
+        assert_eq!(L::decompress_partial(compressed, &mut prefix), 16);
+        assert_eq!(&prefix[..], &original[..16]);
+    }
+
+    #[cfg(feature = "rust-backend")]
+    #[test]
+    fn test_rust_backend_trait_roundtrip() {
+        roundtrip::<crate::rust::RustBackend>();
+    }
+
+    #[cfg(feature = "c-backend")]
+    #[test]
+    fn test_c_backend_trait_roundtrip() {
+        roundtrip::<CBackend>();
+    }
+
+    #[cfg(all(feature = "c-backend", feature = "rust-backend"))]
+    #[test]
+    fn test_cross_backend_equivalence() {
+        let mut rng = rand::thread_rng();
+        let random: Vec<u8> = (0..100_000).map(|_| rng.gen()).collect();
+        let inputs = [
+            b"x".to_vec(),
+            b"tiny".to_vec(),
+            vec![0u8; 1 << 16],
+            b"abcabcabc, cross backend, abcabcabc".repeat(500),
+            (0..=255u8).cycle().take(70_000).collect(),
+            random,
+        ];
+
+        for original in &inputs {
+            let bound = crate::c::compress_bound(original.len() as i32);
+            assert_eq!(bound, crate::rust::compress_bound(original.len() as i32));
+            assert_eq!(bound, unsafe { c::c_lzav_compress_bound(original.len() as i32) });
+
+            let mut compressed = vec![0u8; bound as usize];
+            let mut decompressed = vec![0u8; original.len()];
+
+            let len = crate::c::compress(original, &mut compressed).unwrap();
+            assert_eq!(crate::rust::try_decompress(&compressed[..len], &mut decompressed), Ok(original.len()));
+            assert_eq!(&decompressed, original);
+
+            decompressed.fill(0);
+            let len = crate::rust::compress(original, &mut compressed).unwrap();
+            assert_eq!(crate::c::try_decompress(&compressed[..len], &mut decompressed), Ok(original.len()));
+            assert_eq!(&decompressed, original);
+
+            // Both backends hold `dst` to the exact length the same way
+            let stream = &compressed[..len];
+            for room in [original.len() - 1, original.len() + 100] {
+                let mut c_out = vec![0u8; room];
+                let mut rust_out = vec![0u8; room];
+                assert_eq!(
+                    crate::c::try_decompress(stream, &mut c_out),
+                    crate::rust::try_decompress(stream, &mut rust_out),
+                    "{} bytes into {}",
+                    original.len(),
+                    room
+                );
+                assert_eq!(crate::c::decompress(stream, &mut c_out), crate::rust::decompress(stream, &mut rust_out));
+            }
+        }
+    }
+
     #[test]
     fn test_compress_bound() {
         {
@@ -112,6 +760,44 @@ mod tests {
         }
     }
 
+    // Evaluated at compile time
+    const _: usize = compress_bound_usize(1024).unwrap();
+    const STACK_BOUND: usize = compress_bound(1024) as usize;
+
+    #[test]
+    fn test_const_compress_bound() {
+        let original = b"stack sized buffers ".repeat(52);
+        let mut compressed = [0u8; STACK_BOUND];
+        let compressed_len = compress(&original[..1024], &mut compressed).unwrap();
+
+        let mut decompressed = [0u8; 1024];
+        assert_eq!(try_decompress(&compressed[..compressed_len], &mut decompressed), Ok(1024));
+        assert_eq!(&decompressed[..], &original[..1024]);
+    }
+
+    #[test]
+    fn test_compress_bound_limits() {
+        assert_eq!(compress_bound_usize(0), Some(16));
+        assert_eq!(compress_bound_usize(usize::MAX), None);
+        assert!(compress_bound_usize(usize::MAX / 2).is_some());
+        for srcl in [1, 143, 144, 150, 1 << 20, 1 << 30] {
+            assert_eq!(compress_bound_usize(srcl), Some(compress_bound(srcl as i32) as usize));
+            #[cfg(feature = "c-backend")]
+            assert_eq!(compress_bound(srcl as i32), unsafe { c::c_lzav_compress_bound(srcl as i32) });
+        }
+
+        // The i32 bound saturates instead of wrapping near i32::MAX
+        assert_eq!(compress_bound(i32::MIN), 16);
+        assert_eq!(compress_bound(i32::MAX), i32::MAX);
+        let largest = (0..=i32::MAX)
+            .rev()
+            .step_by(1 << 16)
+            .find(|&srcl| compress_bound_usize(srcl as usize).unwrap() <= i32::MAX as usize)
+            .unwrap();
+        assert_eq!(compress_bound(largest) as usize, compress_bound_usize(largest as usize).unwrap());
+        assert!(compress_bound(largest) >= largest);
+    }
+
     #[test]
     fn test_roundtrip_random_data() {
         let mut rng = rand::thread_rng();
@@ -120,6 +806,311 @@ mod tests {
         verify_roundtrip(&original);
     }
 
+    #[test]
+    fn test_compress_bound_pathological_inputs() {
+        let mut rng = rand::thread_rng();
+        let random: Vec<u8> = (0..1 << 20).map(|_| rng.gen()).collect();
+
+        // Single literals between short references
+        let mut short_runs = Vec::new();
+        for i in 0..20_000u32 {
+            short_runs.push(rng.gen());
+            short_runs.extend_from_slice(b"REFREF");
+            short_runs.push((i % 251) as u8);
+        }
+
+        // Literal runs just long enough to need a varint length before a reference
+        let mut long_runs = Vec::new();
+        for _ in 0..2_000 {
+            long_runs.extend((0..144).map(|_| rng.gen::<u8>()));
+            long_runs.extend_from_slice(b"minref");
+        }
+
+        for input in [&random, &short_runs, &long_runs] {
+            // A buffer of exactly `compress_bound` bytes must always suffice
+            let mut compressed = vec![0u8; compress_bound(input.len() as i32) as usize];
+            let compressed_len = compress(input, &mut compressed).unwrap();
+            assert!(compressed_len <= compressed.len());
+        }
+    }
+
+    #[test]
+    fn test_compress_with_scratch_roundtrip() {
+        let original = b"scratch buffer reuse across calls, ".repeat(200);
+        let mut scratch = vec![0u8; calculate_hash_table_size(original.len() as i32)];
+        let mut compressed = vec![0u8; compress_bound(original.len() as i32) as usize];
+
+        // A too-small scratch buffer falls back to internal allocation
+        for scratch in [&mut scratch[..], &mut [0u8; 16][..]] {
+            let compressed_len = compress_with_scratch(&original, &mut compressed, scratch);
+            assert!(compressed_len > 0);
+
+            let mut decompressed = vec![0u8; original.len()];
+            let result = try_decompress(&compressed[..compressed_len as usize], &mut decompressed);
+            assert_eq!(result, Ok(original.len()));
+            assert_eq!(decompressed, original);
+        }
+    }
+
+    #[test]
+    fn test_compress_with_scratch_sizes() {
+        // Large enough that the table outgrows the C backend's stack buffer
+        let original = b"scratch sized to the input, scratch sized to the input ".repeat(2000);
+        let expected = compress_to_vec(&original).unwrap();
+        let need = calculate_hash_table_size(original.len() as i32);
+        let mut compressed = vec![0u8; compress_bound(original.len() as i32) as usize];
+
+        let mut unaligned = AlignedBuffer::new(need + 4);
+        let cases: [(&str, &mut [u8]); 4] = [
+            ("undersized", &mut AlignedBuffer::new(need / 2)),
+            ("exact", &mut AlignedBuffer::new(need)),
+            ("oversized", &mut AlignedBuffer::new(need * 4)),
+            ("unaligned", &mut unaligned[1..need + 1]),
+        ];
+        for (name, scratch) in cases {
+            let len = compress_with_scratch(&original, &mut compressed, scratch);
+            assert_eq!(&compressed[..len.max(0) as usize], &expected[..], "{name} scratch");
+        }
+    }
+
+    #[test]
+    fn test_compress_level_roundtrip() {
+        let original: Vec<u8> = (0..4000u32)
+            .flat_map(|i| format!("level {} of {}, ", i % 97, i % 13).into_bytes())
+            .collect();
+        let mut sizes = Vec::new();
+
+        for level in [CompressLevel::Fast, CompressLevel::Default, CompressLevel::Max] {
+            let mut compressed = vec![0u8; compress_bound(original.len() as i32) as usize];
+            let compressed_len = compress_level(&original, &mut compressed, level).unwrap();
+            sizes.push(compressed_len);
+
+            let mut decompressed = vec![0u8; original.len()];
+            let result = try_decompress(&compressed[..compressed_len], &mut decompressed);
+            assert_eq!(result, Ok(original.len()));
+            assert_eq!(decompressed, original);
+        }
+        assert!(sizes[2] < sizes[1], "Max should compress better: {:?}", sizes);
+
+        // Incompressible input still fits `compress_bound` at `Max`
+        let mut state = 0x1234_5678u32;
+        let noise: Vec<u8> = (0..10_000)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 24) as u8
+            })
+            .collect();
+        let mut compressed = vec![0u8; compress_bound(noise.len() as i32) as usize];
+        let compressed_len = compress_level(&noise, &mut compressed, CompressLevel::Max).unwrap();
+        let mut decompressed = vec![0u8; noise.len()];
+        assert_eq!(try_decompress(&compressed[..compressed_len], &mut decompressed), Ok(noise.len()));
+        assert_eq!(decompressed, noise);
+    }
+
+    #[test]
+    fn test_result_api_roundtrip() {
+        let original = b"Result API roundtrip, Result API roundtrip, Result API roundtrip";
+        let mut compressed = vec![0u8; compress_bound(original.len() as i32) as usize];
+        let compressed_len = compress(original, &mut compressed).unwrap();
+
+        let mut decompressed = vec![0u8; original.len()];
+        let decompressed_len = try_decompress(&compressed[..compressed_len], &mut decompressed).unwrap();
+        assert_eq!(&decompressed[..decompressed_len], &original[..]);
+    }
+
+    #[test]
+    fn test_result_api_errors() {
+        let mut dst = [0u8; 4];
+        assert_eq!(compress(b"x", &mut []), Err(LzavError::InvalidParams));
+        assert_eq!(compress(b"too long for dst", &mut dst), Err(LzavError::DestOutOfBounds));
+        assert_eq!(LzavError::check(errors::LZAV_E_DSTLEN), Err(LzavError::DestLengthMismatch));
+        assert_eq!(LzavError::Corrupt { at: 7 }.code(), errors::LZAV_E_DSTLEN);
+        assert_eq!(LzavError::Corrupt { at: 7 }.to_string(), "corrupt stream, output diverged at byte 7");
+    }
+
+    #[test]
+    fn test_decompress_into_uninit() {
+        let original = b"uninitialized destination, uninitialized destination".repeat(20);
+        let compressed = compress_to_vec(&original).unwrap();
+
+        let mut dst = Box::new_uninit_slice(original.len());
+        let len = decompress_into_uninit(&compressed, &mut dst).unwrap();
+        assert_eq!(len, original.len());
+        // SAFETY: a successful decode initialized all `len` bytes
+        let dst = unsafe { dst.assume_init() };
+        assert_eq!(&dst[..], &original[..]);
+    }
+
+    #[test]
+    fn test_decompress_partial_prefix() {
+        let original: Vec<u8> = b"prefix of a larger stream, ".repeat(40);
+        let mut compressed = vec![0u8; compress_bound(original.len() as i32) as usize];
+        let compressed_len = compress(&original, &mut compressed).unwrap();
+
+        let mut prefix = vec![0u8; 64];
+        let written = decompress_partial(&compressed[..compressed_len], &mut prefix);
+        assert_eq!(written, 64);
+        assert_eq!(&prefix[..], &original[..64]);
+    }
+
+    #[test]
+    fn test_overlapping_reference_roundtrip() {
+        // Single-byte runs decode through back-references that overlap their output
+        verify_roundtrip(&b"A".repeat(5000));
+    }
+
+    const GOLDEN_INPUT: &[u8] =
+        b"golden vector: abcabcabcabcabc, golden vector: xyzxyzxyzxyz, golden vector!";
+
+    // `GOLDEN_INPUT` compressed by the C backend
+    const GOLDEN_C: &[u8] = &[
+        0x26, 0x40, 0x2b, 0x67, 0x6f, 0x6c, 0x64, 0x65, 0x6e, 0x20, 0x76, 0x65,
+        0x63, 0x74, 0x6f, 0x72, 0x3a, 0x20, 0x61, 0x62, 0x63, 0x61, 0x62, 0x63,
+        0x61, 0x62, 0x63, 0x61, 0x62, 0x63, 0x61, 0x62, 0x63, 0x2c, 0x20, 0x67,
+        0x6f, 0x6c, 0x64, 0x65, 0x6e, 0x20, 0x76, 0x65, 0x63, 0x74, 0x6f, 0x72,
+        0x3a, 0x20, 0x78, 0x79, 0x7a, 0x78, 0x79, 0x7a, 0x78, 0x79, 0x7a, 0x78,
+        0x79, 0x7a, 0xd5, 0x01, 0x06, 0x65, 0x63, 0x74, 0x6f, 0x72, 0x21,
+    ];
+
+    // `GOLDEN_INPUT` compressed by the Rust backend
+    const GOLDEN_RUST: &[u8] = &[
+        0x26, 0x80, 0x05, 0x67, 0x6f, 0x6c, 0x64, 0x65, 0x6e, 0x20, 0x76, 0x65,
+        0x63, 0x74, 0x6f, 0x72, 0x3a, 0x20, 0x61, 0x62, 0x63, 0x61, 0x62, 0x63,
+        0x51, 0x00, 0x05, 0x61, 0x62, 0x63, 0x2c, 0x20, 0x1a, 0x02, 0x86, 0x78,
+        0x79, 0x7a, 0x78, 0x79, 0x7a, 0x51, 0x00, 0x55, 0x07, 0x06, 0x65, 0x63,
+        0x74, 0x6f, 0x72, 0x21,
+    ];
+
+    #[test]
+    fn test_cross_backend_golden_streams() {
+        for stream in [GOLDEN_C, GOLDEN_RUST] {
+            let mut decompressed = vec![0u8; GOLDEN_INPUT.len()];
+            assert_eq!(try_decompress(stream, &mut decompressed), Ok(GOLDEN_INPUT.len()));
+            assert_eq!(decompressed, GOLDEN_INPUT);
+        }
+    }
+
+    #[test]
+    fn test_fmt1_stream_decodes() {
+        let stream = b"\x15\x8Aabcdefghij\x90\x00\x0A\x06klmnop";
+        let expected = [b"abcdefghij".repeat(4), b"klmnop".to_vec()].concat();
+
+        let mut decompressed = vec![0u8; expected.len()];
+        assert_eq!(try_decompress(stream, &mut decompressed), Ok(expected.len()));
+        assert_eq!(decompressed, expected);
+    }
+
+    #[test]
+    fn test_tiny_stream_format() {
+        let original = b"tiny";
+        let mut compressed = vec![0u8; compress_bound(original.len() as i32) as usize];
+        let compressed_len = compress(original, &mut compressed).unwrap();
+        assert_eq!(&compressed[..compressed_len], b"\x26\x04tiny");
+    }
+
+    // Compressed stream of `data` decoded with the active backend
+    fn roundtrip_result(data: &[u8]) -> Result<Vec<u8>, LzavError> {
+        let compressed = compress_to_vec(data)?;
+        decompress_to_vec(&compressed, data.len())
+    }
+
+    #[test]
+    fn test_window_length_boundaries() {
+        // Periodic text, so references reach into the final literals' area
+        let text = |len: usize| -> Vec<u8> {
+            b"window boundary text with a counter: ".iter().cycle().zip(0..len)
+                .map(|(&b, i)| b ^ (i >> 16) as u8)
+                .collect()
+        };
+
+        for len in [LZAV_WIN_LEN - 1, LZAV_WIN_LEN] {
+            let data = text(len);
+            let compressed = compress_to_vec(&data).unwrap();
+            assert!(compressed.len() < len / 4, "{len} bytes to {}", compressed.len());
+            assert_eq!(decompressed_size(&compressed), Ok(len));
+            assert!(decompress_to_vec(&compressed, len).unwrap() == data, "{len} bytes");
+        }
+
+        // Just past the tiny stream limit, where few bytes precede the final literals
+        for len in 33..=48 {
+            let data = text(len);
+            assert_eq!(roundtrip_result(&data).unwrap(), data, "{len} bytes");
+        }
+    }
+
+    mod props {
+        use super::*;
+        use proptest::prelude::*;
+
+        proptest! {
+            #[test]
+            fn prop_roundtrip_tiny(data in prop::collection::vec(any::<u8>(), 0..=32)) {
+                prop_assert_eq!(roundtrip_result(&data)?, data);
+            }
+
+            #[test]
+            fn prop_roundtrip_random(data in prop::collection::vec(any::<u8>(), 1..8192)) {
+                prop_assert_eq!(roundtrip_result(&data)?, data);
+            }
+
+            // A small alphabet produces long, overlapping and back-to-back references
+            #[test]
+            fn prop_roundtrip_repetitive(data in prop::collection::vec(0u8..4, 1..16384)) {
+                prop_assert_eq!(roundtrip_result(&data)?, data);
+            }
+
+            #[test]
+            fn prop_roundtrip_runs(runs in prop::collection::vec((any::<u8>(), 1usize..600), 1..32)) {
+                let data: Vec<u8> = runs.iter().flat_map(|&(b, n)| core::iter::repeat_n(b, n)).collect();
+                prop_assert_eq!(roundtrip_result(&data)?, data);
+            }
+
+            #[test]
+            fn prop_within_bound_random(data in prop::collection::vec(any::<u8>(), 0..8192)) {
+                prop_assert!(unbounded_compressed_len(&data) <= compress_bound(data.len() as i32) as usize);
+            }
+
+            // Literal runs of every length class broken up by minimal
+            // references, so block headers make up as much of the stream as possible
+            #[test]
+            fn prop_within_bound_adversarial(
+                pieces in prop::collection::vec((prop::collection::vec(any::<u8>(), 0..160), 0usize..8), 1..64),
+            ) {
+                let mut data = Vec::new();
+                for (literals, reference) in &pieces {
+                    data.extend_from_slice(literals);
+                    let reference = (*reference).min(data.len());
+                    data.extend_from_within(..reference);
+                }
+                prop_assert!(unbounded_compressed_len(&data) <= compress_bound(data.len() as i32) as usize);
+            }
+        }
+
+        // Compressed length of `data` given far more room than the bound
+        fn unbounded_compressed_len(data: &[u8]) -> usize {
+            let mut dst = vec![0u8; 2 * data.len() + 1024];
+            compress(data, &mut dst).unwrap()
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(4))]
+
+            // Inputs around one window, where the furthest offsets are used
+            #[test]
+            fn prop_roundtrip_near_window(
+                extra in -64isize..64,
+                period in 7usize..4096,
+                seed in any::<u8>(),
+            ) {
+                let len = (LZAV_WIN_LEN as isize + extra) as usize;
+                let data: Vec<u8> = (0..len)
+                    .map(|i| ((i % period) as u8).wrapping_mul(31) ^ seed ^ (i >> 20) as u8)
+                    .collect();
+                prop_assert!(roundtrip_result(&data)? == data);
+            }
+        }
+    }
+
     #[test]
     fn test_html_compression() {
         let data = br#"<!DOCTYPE html>
@@ -1,16 +1,23 @@
+use std::collections::HashSet;
 use std::env;
+use std::fmt;
 use std::fs;
-use std::path::Path;
-use std::io::{self, BufReader, BufWriter, Read, Write, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::io::{BufReader, BufWriter, Read, Write, Seek, SeekFrom};
+use std::time::{Duration, Instant};
+
+use rlzav::{CompressLevel, LzavError};
 
 // Constants for safety limits and buffer sizes
 const MAX_PATH_LENGTH: u32 = 1024;
 const BUFFER_SIZE: usize = 1024 * 1024; // 1MB chunks
 const MAX_FILE_SIZE: u64 = 1024 * 1024 * 1024; // 1GB limit
-
-use rlzav::compress_default;
-use rlzav::decompress;
-use rlzav::errors::{LZAV_E_PARAMS, LZAV_E_SRCOOB, LZAV_E_DSTOOB, LZAV_E_REFOOB, LZAV_E_DSTLEN, LZAV_E_UNKFMT};
+// Every archive starts with this magic and a u32le format version; archives
+// from before the header are refused rather than misread. Version 2 gives
+// each entry's compression level its own byte after the path length
+const ARCHIVE_MAGIC: [u8; 4] = *b"RLZV";
+const ARCHIVE_VERSION: u32 = 2;
+const ARCHIVE_HEADER_LEN: usize = 8;
 
 fn print_help() {
     println!("RLZAV Compression Utility");
@@ -18,17 +25,46 @@ fn print_help() {
     println!("  rlzav <command> [options]");
     println!("\nCOMMANDS:");
     println!("  help                     Show this help message");
-    println!("  compress <input> <out>   Compress a file or folder");
-    println!("  decompress <in> <out>    Decompress an archive");
+    println!("  compress <input> <out> [--level <level>] [--stats]");
+    println!("                           Compress a file or folder");
+    println!("  append <input> <archive> [--level <level>] [--stats]");
+    println!("                           Add a file or folder to an archive,");
+    println!("                           creating it if needed");
+    println!("  decompress <in> <out> [--force] [--to-dir] [--stats]");
+    println!("                           Decompress an archive, refusing to");
+    println!("                           overwrite existing files without --force");
+    println!("  cat <archive> <path>     Write one file from an archive to stdout,");
+    println!("                           decoding a chunk at a time");
+    println!("  list <archive>           List the files in an archive");
+    println!("  verify <archive>         Decompress every file in memory and check");
+    println!("                           its length, writing nothing to disk");
+    println!("\nOPTIONS:");
+    println!("  --level <level>          Compression effort: fast, default or max");
+    println!("                           (default: default). The C backend has no");
+    println!("                           fast mode and compresses fast like default");
+    println!("  --stats                  Print sizes, ratio and throughput as one");
+    println!("                           JSON line on stdout when done");
     println!("\nEXAMPLES:");
     println!("  # Compress a single file:");
     println!("  rlzav compress myfile.txt archive.lzav");
+    println!("\n  # Compress for the best ratio:");
+    println!("  rlzav compress --level max myfile.txt archive.lzav");
     println!("\n  # Compress a folder:");
     println!("  rlzav compress myfolder archive.lzav");
+    println!("\n  # Add more files to an existing archive:");
+    println!("  rlzav append newfolder archive.lzav");
     println!("\n  # Decompress to a folder:");
     println!("  rlzav decompress archive.lzav output_folder");
     println!("\n  # Decompress to a single file:");
     println!("  rlzav decompress archive.lzav output.txt");
+    println!("\n  # Decompress a single-file archive into a folder:");
+    println!("  rlzav decompress archive.lzav output_folder --to-dir");
+    println!("\n  # Show archive contents:");
+    println!("  rlzav list archive.lzav");
+    println!("\n  # Print one file from an archive:");
+    println!("  rlzav cat archive.lzav notes/todo.txt");
+    println!("\n  # Check an archive without extracting it:");
+    println!("  rlzav verify archive.lzav");
 }
 
 fn main() {
@@ -43,31 +79,135 @@ fn main() {
         "help" | "-h" | "--help" => {
             print_help();
         },
-        "compress" => {
-            if args.len() != 4 {
-                eprintln!("Usage: rlzav compress <file/folder> <output_file>");
+        command @ ("compress" | "append") => {
+            let mut stats = false;
+            let mut level = CompressLevel::Default;
+            let mut operands = Vec::new();
+            let mut usage_error = false;
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--stats" => stats = true,
+                    "--level" => match rest.next().and_then(|name| parse_level(name)) {
+                        Some(parsed) => level = parsed,
+                        None => usage_error = true,
+                    },
+                    flag if flag.starts_with('-') => usage_error = true,
+                    _ => operands.push(arg),
+                }
+            }
+            if usage_error || operands.len() != 2 {
+                let target = if command == "append" { "archive_file" } else { "output_file" };
+                eprintln!("Usage: rlzav {} <file/folder> <{}> [--level fast|default|max] [--stats]", command, target);
                 eprintln!("Try 'rlzav help' for more information");
                 std::process::exit(1);
             }
-            let input_folder = &args[2];
-            let output_file = &args[3];
+            if applied_level(level) != level {
+                eprintln!("Warning: this build has no {} level, compressing at {}",
+                    level_name(level), level_name(applied_level(level)));
+            }
+            let input_folder = operands[0];
+            let output_file = operands[1];
 
-            if let Err(e) = compress_folder(input_folder, output_file) {
-                eprintln!("Compression failed: {}", e);
-                std::process::exit(1);
+            let start = Instant::now();
+            let result = if command == "append" {
+                append_folder(input_folder, output_file, level)
+            } else {
+                compress_folder(input_folder, output_file, level)
+            };
+            match result {
+                Ok(totals) if stats => println!("{}", stats_json(command, totals, start.elapsed())),
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("Compression failed: {}", e);
+                    std::process::exit(1);
+                }
             }
         },
         "decompress" => {
+            let (flags, operands): (Vec<&String>, Vec<&String>) =
+                args[2..].iter().partition(|a| a.starts_with('-'));
+            let mut options = ExtractOptions::default();
+            let mut usage_error = operands.len() != 2;
+            for flag in flags {
+                match flag.as_str() {
+                    "--force" | "-f" => options.force = true,
+                    "--to-dir" => options.to_dir = true,
+                    "--stats" => options.stats = true,
+                    _ => usage_error = true,
+                }
+            }
+            if usage_error {
+                eprintln!("Usage: rlzav decompress <archive_file> <output> [--force] [--to-dir] [--stats]");
+                eprintln!("Try 'rlzav help' for more information");
+                std::process::exit(1);
+            }
+            let archive_file = operands[0];
+            let output_folder = operands[1];
+
+            let start = Instant::now();
+            match decompress_archive(archive_file, output_folder, options) {
+                Ok(totals) if options.stats => {
+                    println!("{}", stats_json("decompress", totals, start.elapsed()))
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("Decompression failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        "cat" => {
             if args.len() != 4 {
-                eprintln!("Usage: rlzav decompress <archive_file> <output>");
+                eprintln!("Usage: rlzav cat <archive_file> <path>");
                 eprintln!("Try 'rlzav help' for more information");
                 std::process::exit(1);
             }
-            let archive_file = &args[2];
-            let output_folder = &args[3];
 
-            if let Err(e) = decompress_archive(archive_file, output_folder) {
-                eprintln!("Decompression failed: {}", e);
+            let mut stdout = BufWriter::new(std::io::stdout().lock());
+            if let Err(e) = cat_entry(&args[2], &args[3], &mut stdout) {
+                eprintln!("Extraction failed: {}", e);
+                std::process::exit(1);
+            }
+        },
+        "list" => {
+            if args.len() != 3 {
+                eprintln!("Usage: rlzav list <archive_file>");
+                eprintln!("Try 'rlzav help' for more information");
+                std::process::exit(1);
+            }
+
+            if let Err(e) = list_archive(&args[2]) {
+                eprintln!("Listing failed: {}", e);
+                std::process::exit(1);
+            }
+        },
+        "verify" => {
+            if args.len() != 3 {
+                eprintln!("Usage: rlzav verify <archive_file>");
+                eprintln!("Try 'rlzav help' for more information");
+                std::process::exit(1);
+            }
+
+            let entries = match verify_archive(&args[2]) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    eprintln!("Verification failed: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let mut failed = 0;
+            for entry in &entries {
+                match &entry.result {
+                    Ok(len) => println!("OK    {} ({} bytes)", entry.path, len),
+                    Err(reason) => {
+                        println!("FAIL  {}: {}", entry.path, reason);
+                        failed += 1;
+                    }
+                }
+            }
+            println!("{} of {} files verified", entries.len() - failed, entries.len());
+            if failed > 0 {
                 std::process::exit(1);
             }
         },
@@ -79,45 +219,243 @@ fn main() {
     }
 }
 
-fn compress_folder(input: &str, output: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let path = Path::new(input);
+// Byte counts over every file an archive command processed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Totals {
+    original: u64,
+    compressed: u64,
+}
+
+impl Totals {
+    fn add(&mut self, original: u64, compressed: u64) {
+        self.original += original;
+        self.compressed += compressed;
+    }
+}
+
+// One JSON line for `--stats`; throughput counts original bytes
+fn stats_json(operation: &str, totals: Totals, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    let ratio = if totals.original == 0 { 0.0 } else { totals.compressed as f64 / totals.original as f64 };
+    let mb_per_sec = if secs > 0.0 { totals.original as f64 / 1e6 / secs } else { 0.0 };
+    format!(
+        "{{\"operation\":\"{}\",\"original_bytes\":{},\"compressed_bytes\":{},\"ratio\":{:.4},\"elapsed_secs\":{:.6},\"mb_per_sec\":{:.2}}}",
+        operation, totals.original, totals.compressed, ratio, secs, mb_per_sec
+    )
+}
+
+fn parse_level(name: &str) -> Option<CompressLevel> {
+    match name {
+        "fast" => Some(CompressLevel::Fast),
+        "default" => Some(CompressLevel::Default),
+        "max" => Some(CompressLevel::Max),
+        _ => None,
+    }
+}
+
+fn level_name(level: CompressLevel) -> &'static str {
+    match level {
+        CompressLevel::Fast => "fast",
+        CompressLevel::Default => "default",
+        CompressLevel::Max => "max",
+    }
+}
+
+// The level `rlzav::compress_level` applies, which entry headers record. The
+// C backend has no fast mode, so `fast` compresses like `default` there
+fn applied_level(level: CompressLevel) -> CompressLevel {
+    if cfg!(feature = "c-backend") && level == CompressLevel::Fast {
+        CompressLevel::Default
+    } else {
+        level
+    }
+}
+
+// Level codes stored in entry headers
+fn level_code(level: CompressLevel) -> u8 {
+    match level {
+        CompressLevel::Default => 0,
+        CompressLevel::Fast => 1,
+        CompressLevel::Max => 2,
+    }
+}
+
+fn level_from_code(code: u8) -> Option<CompressLevel> {
+    match code {
+        0 => Some(CompressLevel::Default),
+        1 => Some(CompressLevel::Fast),
+        2 => Some(CompressLevel::Max),
+        _ => None,
+    }
+}
+
+// Archive failures that name the file involved, and for a chunk that fails
+// to decode the decoder's own error, so a corrupt archive can be diagnosed
+// without guessing which entry or which check failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CliError {
+    ArchiveTruncated { path: String },
+    BadPathLength { len: u32 },
+    UnknownLevel { code: u8 },
+    InvalidChunkHeader { path: String, chunk: u32 },
+    Decompress { path: String, chunk: u32, error: LzavError },
+    LengthMismatch { path: String, expected: u64, decoded: u64 },
+    FileTooLarge { path: String, len: u64 },
+    UnsupportedArchive { version: Option<u32> },
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::ArchiveTruncated { path } => write!(f, "Archive truncated in {}", path),
+            CliError::BadPathLength { len } => write!(f, "Invalid path length {} in archive", len),
+            CliError::UnknownLevel { code } => write!(f, "Unknown compression level {} in archive", code),
+            CliError::InvalidChunkHeader { path, chunk } => {
+                write!(f, "Invalid header for chunk {} of {}", chunk, path)
+            }
+            CliError::Decompress { path, chunk, error } => {
+                write!(f, "Decompression failed for {} in chunk {}: {}", path, chunk, error)
+            }
+            CliError::LengthMismatch { path, expected, decoded } => {
+                write!(f, "Decompression failed for {}: decoded {} bytes, expected {}", path, decoded, expected)
+            }
+            CliError::FileTooLarge { path, len } => {
+                write!(f, "{} is too large: {} bytes, the limit is {}", path, len, MAX_FILE_SIZE)
+            }
+            CliError::UnsupportedArchive { version: Some(version) } => {
+                write!(f, "Unsupported archive version {} (this build reads {})", version, ARCHIVE_VERSION)
+            }
+            CliError::UnsupportedArchive { version: None } => {
+                write!(f, "Unsupported archive version: no rlzav header, recreate the archive")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CliError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CliError::Decompress { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+fn compress_folder(input: &str, output: &str, level: CompressLevel) -> Result<Totals, Box<dyn std::error::Error>> {
     let file = fs::File::create(output)?;
     let mut archive = BufWriter::new(file);
+    write_archive_header(&mut archive)?;
+    let totals = write_entries(input, &mut archive, level)?;
+    archive.flush()?;
+    Ok(totals)
+}
+
+// Add the entries for `input` after those already in `output`, creating it
+// when missing. The file is not opened in append mode: entries patch their
+// chunk count after the data, which `O_APPEND` would write at the end
+fn append_folder(input: &str, output: &str, level: CompressLevel) -> Result<Totals, Box<dyn std::error::Error>> {
+    let file = fs::OpenOptions::new().write(true).create(true).truncate(false).open(output)?;
+
+    // Refuse to grow a file that does not parse as an archive up to its end;
+    // an empty one was just created and gets its header
+    let original_len = file.metadata()?.len();
+    if original_len > 0 {
+        read_archive_index(output)?;
+    }
+
+    let mut archive = BufWriter::new(file);
+    archive.seek(SeekFrom::End(0))?;
+    let mut write = || -> Result<Totals, Box<dyn std::error::Error>> {
+        if original_len == 0 {
+            write_archive_header(&mut archive)?;
+        }
+        let totals = write_entries(input, &mut archive, level)?;
+        archive.flush()?;
+        Ok(totals)
+    };
+    let written = write();
+
+    // A failed append leaves the archive as it was
+    if written.is_err() {
+        let (file, _) = archive.into_parts();
+        if let Err(e) = file.set_len(original_len) {
+            eprintln!("Could not restore {}: {}", output, e);
+        }
+    }
+    written
+}
+
+// Write an entry for the file `input`, or for every file under the folder
+// `input`, at the archive's current position
+fn write_entries(input: &str, archive: &mut BufWriter<fs::File>, level: CompressLevel)
+    -> Result<Totals, Box<dyn std::error::Error>>
+{
+    let path = Path::new(input);
+    let mut totals = Totals::default();
 
     if path.is_file() {
         let metadata = fs::metadata(path)?;
         if metadata.len() > MAX_FILE_SIZE {
-            return Err("File too large".into());
+            return Err(CliError::FileTooLarge { path: input.to_string(), len: metadata.len() }.into());
         }
         let file_name = path.file_name()
             .ok_or("Invalid file name")?
             .to_string_lossy()
             .into_owned();
-        compress_single_file(&mut archive, path, &file_name)?;
+        let (original, compressed) = compress_single_file(archive, path, &file_name, level)?;
+        totals.add(original, compressed);
     } else {
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file() {
-                let metadata = fs::metadata(&path)?;
-                if metadata.len() > MAX_FILE_SIZE {
-                    eprintln!("Skipping large file: {}", path.display());
-                    continue;
-                }
-                let relative_path = path.file_name()
-                    .ok_or("Invalid file name")?
-                    .to_string_lossy()
-                    .into_owned();
-                compress_single_file(&mut archive, &path, &relative_path)?;
+        let mut files = Vec::new();
+        let mut visited = HashSet::new();
+        collect_files(path, "", &mut files, &mut visited)?;
+
+        for (path, relative_path) in files {
+            let metadata = fs::metadata(&path)?;
+            if metadata.len() > MAX_FILE_SIZE {
+                eprintln!("Skipping large file: {}", path.display());
+                continue;
             }
+            let (original, compressed) = compress_single_file(archive, &path, &relative_path, level)?;
+            totals.add(original, compressed);
+        }
+    }
+    Ok(totals)
+}
+
+// Recursively list the files under `dir` with their archive paths, which are
+// relative to the input folder and `/`-separated. Directories are tracked by
+// canonical path so symlink loops are only walked once.
+fn collect_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut Vec<(PathBuf, String)>,
+    visited: &mut HashSet<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !visited.insert(fs::canonicalize(dir)?) {
+        eprintln!("Skipping already visited directory: {}", dir.display());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let relative_path = format!("{}{}", prefix, name);
+
+        if path.is_dir() {
+            collect_files(&path, &format!("{}/", relative_path), files, visited)?;
+        } else if path.is_file() {
+            files.push((path, relative_path));
         }
     }
-    archive.flush()?;
     Ok(())
 }
 
-fn compress_single_file(archive: &mut BufWriter<fs::File>, path: &Path, store_path: &str) 
-    -> Result<(), Box<dyn std::error::Error>> 
+// Returns the file's original and compressed sizes
+fn compress_single_file(archive: &mut BufWriter<fs::File>, path: &Path, store_path: &str, level: CompressLevel)
+    -> Result<(u64, u64), Box<dyn std::error::Error>> 
 {
     let path_bytes = store_path.as_bytes();
     if path_bytes.len() > MAX_PATH_LENGTH as usize {
@@ -128,110 +466,859 @@ fn compress_single_file(archive: &mut BufWriter<fs::File>, path: &Path, store_pa
     let mut reader = BufReader::new(file);
     let file_size = reader.get_ref().metadata()?.len();
     
-    let path_len = path_bytes.len() as u32;
-    archive.write_all(&path_len.to_le_bytes())?;
+    let level = applied_level(level);
+    archive.write_all(&(path_bytes.len() as u32).to_le_bytes())?;
+    archive.write_all(&[level_code(level)])?;
     archive.write_all(path_bytes)?;
     archive.write_all(&(file_size as u32).to_le_bytes())?;
 
-    // Stream compression in chunks
+    // Stream compression in chunks, each framed as
+    // [original_len: u32le][compressed_len: u32le][data]
     let mut buffer = vec![0u8; BUFFER_SIZE];
-    let mut compressed_size = 0u32;
-    let compressed_size_pos = archive.seek(SeekFrom::Current(0))?;
-    archive.write_all(&[0u8; 4])?; // Placeholder for compressed size
+    let mut compressed = vec![0u8; rlzav::compress_bound(BUFFER_SIZE as i32) as usize];
+    let mut compressed_size = 0u64;
+    let mut chunk_count = 0u32;
+    let chunk_count_pos = archive.stream_position()?;
+    archive.write_all(&[0u8; 4])?; // Placeholder for chunk count
 
     eprintln!("Compressing file: {}", path.display());
     
     loop {
-        let bytes_read = reader.read(&mut buffer)?;
+        let bytes_read = read_chunk(&mut reader, &mut buffer)?;
         if bytes_read == 0 { break; }
         
         let chunk = &buffer[..bytes_read];
-        let mut compressed = vec![0u8; rlzav::compress_bound(bytes_read as i32) as usize];
-        let compressed_len = rlzav::compress_default(chunk, &mut compressed);
-        compressed.truncate(compressed_len as usize);
-        
-        compressed_size += compressed_len as u32;
-        archive.write_all(&compressed)?;
+        let compressed_len = rlzav::compress_level(chunk, &mut compressed, level)?;
+
+        archive.write_all(&(bytes_read as u32).to_le_bytes())?;
+        archive.write_all(&(compressed_len as u32).to_le_bytes())?;
+        archive.write_all(&compressed[..compressed_len])?;
+
+        compressed_size += compressed_len as u64;
+        chunk_count += 1;
     }
 
-    // Go back and write the actual compressed size
-    let current_pos = archive.seek(SeekFrom::Current(0))?;
-    archive.seek(SeekFrom::Start(compressed_size_pos))?;
-    archive.write_all(&compressed_size.to_le_bytes())?;
+    // Go back and write the actual chunk count
+    let current_pos = archive.stream_position()?;
+    archive.seek(SeekFrom::Start(chunk_count_pos))?;
+    archive.write_all(&chunk_count.to_le_bytes())?;
     archive.seek(SeekFrom::Start(current_pos))?;
 
     eprintln!("Saved compressed file: {} ({} bytes -> {} bytes)", 
              path.display(), file_size, compressed_size);
+    Ok((file_size, compressed_size))
+}
+
+// Fill `buffer` as far as possible, returning fewer bytes only at end of file
+fn read_chunk(reader: &mut impl Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn write_archive_header(archive: &mut impl Write) -> std::io::Result<()> {
+    archive.write_all(&ARCHIVE_MAGIC)?;
+    archive.write_all(&ARCHIVE_VERSION.to_le_bytes())
+}
+
+// Check the magic and version an archive starts with, leaving `reader` at
+// its first entry
+fn read_archive_header(reader: &mut impl Read) -> Result<(), Box<dyn std::error::Error>> {
+    let mut header = [0u8; ARCHIVE_HEADER_LEN];
+    if read_chunk(reader, &mut header)? < ARCHIVE_HEADER_LEN || header[..4] != ARCHIVE_MAGIC {
+        return Err(CliError::UnsupportedArchive { version: None }.into());
+    }
+    let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+    if version != ARCHIVE_VERSION {
+        return Err(CliError::UnsupportedArchive { version: Some(version) }.into());
+    }
+    Ok(())
+}
+
+struct EntryHeader {
+    path: String,
+    original_len: u32,
+    chunk_count: u32,
+    level: CompressLevel,
+}
+
+// Read an entry's path length, compression level, path, original length
+// and chunk count
+fn read_entry_header(reader: &mut impl Read) -> Result<EntryHeader, Box<dyn std::error::Error>> {
+    let path_len = read_u32(reader)?;
+    if path_len > MAX_PATH_LENGTH {
+        return Err(CliError::BadPathLength { len: path_len }.into());
+    }
+    let mut code = [0u8];
+    reader.read_exact(&mut code)?;
+    let level = level_from_code(code[0]).ok_or(CliError::UnknownLevel { code: code[0] })?;
+
+    let mut path_bytes = vec![0u8; path_len as usize];
+    reader.read_exact(&mut path_bytes)?;
+    let path = String::from_utf8(path_bytes)?;
+
+    let original_len = read_u32(reader)?;
+    let chunk_count = read_u32(reader)?;
+
+    if original_len as u64 > MAX_FILE_SIZE {
+        return Err(CliError::FileTooLarge { path, len: original_len as u64 }.into());
+    }
+    Ok(EntryHeader { path, original_len, chunk_count, level })
+}
+
+struct ArchiveEntry {
+    path: String,
+    original_len: u64,
+    compressed_len: u64,
+    level: CompressLevel,
+}
+
+// Walk the entry and chunk headers, seeking past the compressed data
+fn read_archive_index(archive: &str) -> Result<Vec<ArchiveEntry>, Box<dyn std::error::Error>> {
+    let file = fs::File::open(archive)?;
+    let archive_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    read_archive_header(&mut reader)?;
+    let mut entries = Vec::new();
+
+    while reader.stream_position()? < archive_len {
+        let EntryHeader { path, original_len, chunk_count, level } = read_entry_header(&mut reader)?;
+        let mut compressed_len = 0u64;
+
+        for _ in 0..chunk_count {
+            let _chunk_len = read_u32(&mut reader)?;
+            let chunk_compressed_len = read_u32(&mut reader)?;
+            reader.seek_relative(chunk_compressed_len as i64)?;
+            if reader.stream_position()? > archive_len {
+                return Err(CliError::ArchiveTruncated { path }.into());
+            }
+            compressed_len += chunk_compressed_len as u64;
+        }
+
+        entries.push(ArchiveEntry {
+            path,
+            original_len: original_len as u64,
+            compressed_len,
+            level,
+        });
+    }
+    Ok(entries)
+}
+
+fn list_archive(archive: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = read_archive_index(archive)?;
+    let ratio = |compressed: u64, original: u64| {
+        if original == 0 { 0.0 } else { compressed as f64 / original as f64 * 100.0 }
+    };
+
+    println!("{:>12} {:>12} {:>7} {:>7}  Name", "Original", "Compressed", "Ratio", "Level");
+    let (mut total_original, mut total_compressed) = (0u64, 0u64);
+    for entry in &entries {
+        println!("{:>12} {:>12} {:>6.2}% {:>7}  {}",
+            entry.original_len, entry.compressed_len,
+            ratio(entry.compressed_len, entry.original_len), level_name(entry.level), entry.path);
+        total_original += entry.original_len;
+        total_compressed += entry.compressed_len;
+    }
+    println!("{:>12} {:>12} {:>6.2}% {:>7}  {} files",
+        total_original, total_compressed,
+        ratio(total_compressed, total_original), "", entries.len());
     Ok(())
 }
 
-fn decompress_archive(archive: &str, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+// An entry checked by `verify_archive`: its decoded length, or why it failed
+struct VerifiedEntry {
+    path: String,
+    result: Result<u64, String>,
+}
+
+// Decompress every entry into a scratch buffer and check the chunk and file
+// lengths. A bad entry is reported and the walk continues; only a damaged
+// layout that hides where the next entry starts ends it with an error
+fn verify_archive(archive: &str) -> Result<Vec<VerifiedEntry>, Box<dyn std::error::Error>> {
+    let file = fs::File::open(archive)?;
+    let archive_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    read_archive_header(&mut reader)?;
+    let mut entries = Vec::new();
+    let mut compressed = Vec::new();
+    let mut decompressed = Vec::new();
+
+    while reader.stream_position()? < archive_len {
+        let EntryHeader { path, original_len, chunk_count, .. } = read_entry_header(&mut reader)?;
+        let mut problem = None;
+        let mut decoded = 0u64;
+
+        for i in 0..chunk_count {
+            let chunk_len = read_u32(&mut reader)? as usize;
+            let compressed_len = read_u32(&mut reader)? as usize;
+            if compressed_len as u64 > archive_len - reader.stream_position()? {
+                return Err(CliError::ArchiveTruncated { path }.into());
+            }
+            compressed.resize(compressed_len, 0);
+            reader.read_exact(&mut compressed)?;
+
+            // Later chunks are still read to reach the next entry
+            if problem.is_some() {
+                continue;
+            }
+            if chunk_len == 0
+                || chunk_len > BUFFER_SIZE
+                || compressed_len > rlzav::compress_bound(chunk_len as i32) as usize
+            {
+                problem = Some(format!("invalid header for chunk {}", i));
+                continue;
+            }
+
+            decompressed.resize(chunk_len, 0);
+            match rlzav::try_decompress(&compressed, &mut decompressed) {
+                Ok(len) if len == chunk_len => decoded += len as u64,
+                Ok(len) => problem = Some(format!("chunk {} decoded to {} bytes, expected {}", i, len, chunk_len)),
+                Err(e) => problem = Some(format!("chunk {}: {}", i, e)),
+            }
+        }
+
+        let result = match problem {
+            Some(problem) => Err(problem),
+            None if decoded != original_len as u64 => {
+                Err(format!("decoded {} bytes, expected {}", decoded, original_len))
+            }
+            None => Ok(decoded),
+        };
+        entries.push(VerifiedEntry { path, result });
+    }
+    Ok(entries)
+}
+
+// Where an entry stored as `path` is extracted to
+fn extract_path(output_path: &Path, is_dir: bool, path: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if !is_dir {
+        return Ok(output_path.to_path_buf());
+    }
+
+    // Stored paths are relative and `/`-separated; refuse anything that
+    // would land outside the output folder, such as `..` or absolute paths
+    let relative = Path::new(path);
+    if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!("Invalid path in archive: {}", path).into());
+    }
+    Ok(output_path.join(relative))
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ExtractOptions {
+    force: bool,  // Overwrite existing files
+    to_dir: bool, // Extract into a folder even for a single entry
+    stats: bool,  // Print `--stats` JSON when done
+}
+
+fn decompress_archive(archive: &str, output: &str, options: ExtractOptions) -> Result<Totals, Box<dyn std::error::Error>> {
     eprintln!("Starting decompression of archive: {}", archive);
     let file = fs::File::open(archive)?;
     let metadata = file.metadata()?;
     if metadata.len() > MAX_FILE_SIZE {
-        return Err("Archive too large".into());
+        return Err(CliError::FileTooLarge { path: archive.to_string(), len: metadata.len() }.into());
     }
 
-    let mut reader = BufReader::new(file);
+    let entries = read_archive_index(archive)?;
     let output_path = Path::new(output);
-    let is_dir = output_path.extension().is_none() || 
-                 output_path.to_str().map_or(false, |s| s.ends_with('/'));
 
-    while reader.seek(SeekFrom::Current(0))? < metadata.len() {
-        let mut path_len_bytes = [0u8; 4];
-        reader.read_exact(&mut path_len_bytes)?;
-        let path_len = u32::from_le_bytes(path_len_bytes);
+    // Extract into a folder when asked to, when one exists there, or when
+    // several entries could not share a single output file
+    let is_dir = options.to_dir
+        || output_path.is_dir()
+        || output.ends_with('/')
+        || output.ends_with(std::path::MAIN_SEPARATOR)
+        || entries.len() > 1;
+    if is_dir && output_path.exists() && !output_path.is_dir() {
+        return Err(format!(
+            "Cannot extract {} entries into {}: it is not a directory",
+            entries.len(),
+            output_path.display()
+        ).into());
+    }
 
-        if path_len > MAX_PATH_LENGTH {
-            return Err("Invalid path length in archive".into());
+    // Check every destination up front so a refused extraction writes nothing
+    if !options.force {
+        let mut conflicts = Vec::new();
+        for entry in &entries {
+            let final_path = extract_path(output_path, is_dir, &entry.path)?;
+            if final_path.symlink_metadata().is_ok() {
+                conflicts.push(final_path.display().to_string());
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(format!(
+                "Refusing to overwrite existing files (use --force): {}",
+                conflicts.join(", ")
+            ).into());
         }
+    }
 
-        let mut path_bytes = vec![0u8; path_len as usize];
-        reader.read_exact(&mut path_bytes)?;
-        let path = String::from_utf8(path_bytes)?;
+    let mut totals = Totals::default();
+    let mut reader = BufReader::new(file);
+    read_archive_header(&mut reader)?;
+    while reader.stream_position()? < metadata.len() {
+        let EntryHeader { path, original_len, chunk_count, .. } = read_entry_header(&mut reader)?;
 
-        let mut original_len_bytes = [0u8; 4];
-        reader.read_exact(&mut original_len_bytes)?;
-        let original_len = u32::from_le_bytes(original_len_bytes);
+        eprintln!("Extracting: {} ({} chunks)", path, chunk_count);
 
-        let mut compressed_len_bytes = [0u8; 4];
-        reader.read_exact(&mut compressed_len_bytes)?;
-        let compressed_len = u32::from_le_bytes(compressed_len_bytes);
+        let final_path = extract_path(output_path, is_dir, &path)?;
+        if let Some(parent) = final_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
 
-        if compressed_len > MAX_FILE_SIZE as u32 || original_len > MAX_FILE_SIZE as u32 {
-            return Err("File in archive too large".into());
+        // `create_new` also refuses a file that appeared after the check
+        let output_file = if options.force {
+            fs::File::create(&final_path)
+        } else {
+            fs::OpenOptions::new().write(true).create_new(true).open(&final_path)
         }
+        .map_err(|e| format!("Cannot create {}: {}", final_path.display(), e))?;
+        let mut output_file = BufWriter::new(output_file);
+        let written = extract_entry(&mut reader, &path, original_len, chunk_count, &mut output_file, &mut totals)?;
+        output_file.flush()?;
 
-        eprintln!("Extracting: {} ({} bytes compressed)", path, compressed_len);
+        eprintln!("Extracted: {} ({} bytes)", final_path.display(), written);
+    }
 
-        let final_path = if is_dir {
-            output_path.join(&path)
-        } else {
-            output_path.to_path_buf()
-        };
+    eprintln!("Decompression completed successfully.");
+    Ok(totals)
+}
 
-        if let Some(parent) = final_path.parent() {
-            fs::create_dir_all(parent)?;
+// Decode an entry's chunks one at a time and write each to `out`, so memory
+// stays bounded by the chunk size however large the entry is
+fn extract_entry(
+    reader: &mut impl Read,
+    path: &str,
+    original_len: u32,
+    chunk_count: u32,
+    out: &mut impl Write,
+    totals: &mut Totals,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut compressed = Vec::new();
+    let mut decompressed = Vec::new();
+    let mut written = 0u64;
+
+    for chunk in 0..chunk_count {
+        let chunk_len = read_u32(reader)? as usize;
+        let compressed_len = read_u32(reader)? as usize;
+
+        if chunk_len == 0
+            || chunk_len > BUFFER_SIZE
+            || compressed_len > rlzav::compress_bound(chunk_len as i32) as usize
+        {
+            return Err(CliError::InvalidChunkHeader { path: path.to_string(), chunk }.into());
         }
 
-        let mut compressed = vec![0u8; compressed_len as usize];
+        compressed.resize(compressed_len, 0);
         reader.read_exact(&mut compressed)?;
 
-        let mut decompressed = vec![0u8; original_len as usize];
-        let result = rlzav::decompress(&compressed, &mut decompressed);
+        decompressed.resize(chunk_len, 0);
+        let decompress_error = |error| CliError::Decompress { path: path.to_string(), chunk, error };
+        let result = rlzav::try_decompress(&compressed, &mut decompressed).map_err(decompress_error)?;
+        if result != chunk_len {
+            return Err(decompress_error(LzavError::DestLengthMismatch).into());
+        }
 
-        if result < 0 {
-            return Err(format!("Decompression failed for {}: {}", path, result).into());
+        out.write_all(&decompressed)?;
+        written += result as u64;
+        totals.add(result as u64, compressed_len as u64);
+    }
+
+    if written != original_len as u64 {
+        return Err(CliError::LengthMismatch {
+            path: path.to_string(),
+            expected: original_len as u64,
+            decoded: written,
         }
+        .into());
+    }
+    Ok(written)
+}
 
-        let mut output_file = BufWriter::new(fs::File::create(&final_path)?);
-        output_file.write_all(&decompressed[..result as usize])?;
-        output_file.flush()?;
+// Stream the entry stored as `path` to `out`, skipping the others unread
+fn cat_entry(archive: &str, path: &str, out: &mut impl Write) -> Result<u64, Box<dyn std::error::Error>> {
+    let file = fs::File::open(archive)?;
+    let archive_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    read_archive_header(&mut reader)?;
 
-        eprintln!("Extracted: {} ({} bytes)", final_path.display(), result);
+    while reader.stream_position()? < archive_len {
+        let header = read_entry_header(&mut reader)?;
+        if header.path == path {
+            let mut totals = Totals::default();
+            let written =
+                extract_entry(&mut reader, path, header.original_len, header.chunk_count, out, &mut totals)?;
+            out.flush()?;
+            return Ok(written);
+        }
+        for _ in 0..header.chunk_count {
+            let _chunk_len = read_u32(&mut reader)?;
+            let chunk_compressed_len = read_u32(&mut reader)?;
+            reader.seek_relative(chunk_compressed_len as i64)?;
+        }
     }
+    Err(format!("No entry {} in {}", path, archive).into())
+}
 
-    eprintln!("Decompression completed successfully.");
-    Ok(())
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory under the system temp dir, removed on drop so a
+    // failing assertion does not leave it behind
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("rlzav-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_archive_roundtrip_multi_chunk() {
+        let dir = TempDir::new("archive");
+        let input = dir.join("input.bin");
+        let archive = dir.join("archive.lzav");
+        let output = dir.join("output.bin");
+
+        // 5MB spanning several chunks, mixing repeated text and noise
+        let mut state = 0x2545F491u32;
+        let data: Vec<u8> = (0..5 * 1024 * 1024)
+            .map(|i: usize| {
+                if (i / 4096).is_multiple_of(2) {
+                    b"archive chunk framing "[i % 22]
+                } else {
+                    state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                    (state >> 24) as u8
+                }
+            })
+            .collect();
+        fs::write(&input, &data).unwrap();
+
+        compress_folder(input.to_str().unwrap(), archive.to_str().unwrap(), CompressLevel::Default).unwrap();
+        decompress_archive(archive.to_str().unwrap(), output.to_str().unwrap(), ExtractOptions::default()).unwrap();
+        let restored = fs::read(&output).unwrap();
+
+        assert!(restored == data);
+    }
+
+    #[test]
+    fn test_archive_roundtrip_nested_folders() {
+        let dir = TempDir::new("nested");
+        let input = dir.join("input");
+        let archive = dir.join("archive.lzav");
+        let output = dir.join("output");
+
+        let files = [
+            ("top.txt", b"top level file ".repeat(10)),
+            ("sub/middle.txt", b"one level down ".repeat(20)),
+            ("sub/deeper/bottom.txt", b"two levels down ".repeat(30)),
+            ("other/empty.txt", Vec::new()),
+        ];
+        for (name, contents) in &files {
+            let path = input.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        // A link back to the root must not be walked again
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&input, input.join("sub/loop")).unwrap();
+
+        compress_folder(input.to_str().unwrap(), archive.to_str().unwrap(), CompressLevel::Default).unwrap();
+        decompress_archive(archive.to_str().unwrap(), output.to_str().unwrap(), ExtractOptions::default()).unwrap();
+
+        let restored: Vec<_> = files
+            .iter()
+            .map(|(name, _)| fs::read(output.join(name)).unwrap())
+            .collect();
+        let loop_restored = output.join("sub/loop").exists();
+
+        for ((_, contents), restored) in files.iter().zip(restored) {
+            assert_eq!(&restored, contents);
+        }
+        assert!(!loop_restored);
+    }
+
+    #[test]
+    fn test_append_grows_archive() {
+        let dir = TempDir::new("append");
+        let first = dir.join("first");
+        let second = dir.join("second.txt");
+        let third = dir.join("third");
+        let archive = dir.join("archive.lzav");
+        let output = dir.join("output");
+        fs::create_dir_all(first.join("sub")).unwrap();
+        fs::create_dir_all(&third).unwrap();
+        fs::write(first.join("a.txt"), b"first batch ".repeat(100)).unwrap();
+        fs::write(first.join("sub/b.txt"), b"first batch, nested ".repeat(50)).unwrap();
+        fs::write(&second, b"appended single file ".repeat(70)).unwrap();
+        fs::write(third.join("c.txt"), b"appended folder ".repeat(90)).unwrap();
+
+        let archive_str = archive.to_str().unwrap();
+        compress_folder(first.to_str().unwrap(), archive_str, CompressLevel::Default).unwrap();
+        let first_len = fs::metadata(&archive).unwrap().len();
+        append_folder(second.to_str().unwrap(), archive_str, CompressLevel::Fast).unwrap();
+        append_folder(third.to_str().unwrap(), archive_str, CompressLevel::Max).unwrap();
+        let appended_len = fs::metadata(&archive).unwrap().len();
+        let entries = read_archive_index(archive_str).unwrap();
+        decompress_archive(archive_str, output.to_str().unwrap(), ExtractOptions::default()).unwrap();
+        let restored: Vec<_> = ["a.txt", "sub/b.txt", "second.txt", "c.txt"]
+            .iter()
+            .map(|name| fs::read(output.join(name)).unwrap())
+            .collect();
+
+        // A file that is not an archive is left untouched
+        let not_archive = dir.join("notes.txt");
+        fs::write(&not_archive, b"plain text").unwrap();
+        let refused = append_folder(second.to_str().unwrap(), not_archive.to_str().unwrap(), CompressLevel::Default);
+        let notes = fs::read(&not_archive).unwrap();
+
+        // Appending to a missing archive creates it, header included
+        let created = dir.join("created.lzav");
+        append_folder(second.to_str().unwrap(), created.to_str().unwrap(), CompressLevel::Default).unwrap();
+        let created_entries = read_archive_index(created.to_str().unwrap()).unwrap();
+
+        assert!(appended_len > first_len);
+        let listed: Vec<_> = entries.iter().map(|e| (e.path.as_str(), e.level)).collect();
+        assert_eq!(
+            listed,
+            [
+                ("a.txt", CompressLevel::Default),
+                ("sub/b.txt", CompressLevel::Default),
+                ("second.txt", applied_level(CompressLevel::Fast)),
+                ("c.txt", CompressLevel::Max),
+            ]
+        );
+        assert_eq!(restored[0], b"first batch ".repeat(100));
+        assert_eq!(restored[1], b"first batch, nested ".repeat(50));
+        assert_eq!(restored[2], b"appended single file ".repeat(70));
+        assert_eq!(restored[3], b"appended folder ".repeat(90));
+        assert!(refused.is_err());
+        assert_eq!(notes, b"plain text");
+        assert_eq!(created_entries.len(), 1);
+        assert_eq!(created_entries[0].path, "second.txt");
+    }
+
+    #[test]
+    fn test_list_archive_index() {
+        let dir = TempDir::new("list");
+        let input = dir.join("input");
+        let archive = dir.join("archive.lzav");
+        fs::create_dir_all(input.join("sub")).unwrap();
+        fs::write(input.join("a.txt"), b"listed file ".repeat(100)).unwrap();
+        fs::write(input.join("sub/b.txt"), b"nested listed file ".repeat(50)).unwrap();
+
+        compress_folder(input.to_str().unwrap(), archive.to_str().unwrap(), CompressLevel::Default).unwrap();
+        let entries = read_archive_index(archive.to_str().unwrap()).unwrap();
+
+        // Cutting into the last chunk's data must be reported, not ignored
+        let bytes = fs::read(&archive).unwrap();
+        fs::write(&archive, &bytes[..bytes.len() - 1]).unwrap();
+        let truncated = read_archive_index(archive.to_str().unwrap());
+
+        let listed: Vec<_> = entries.iter().map(|e| (e.path.as_str(), e.original_len)).collect();
+        assert_eq!(listed, [("a.txt", 1200), ("sub/b.txt", 950)]);
+        assert!(entries.iter().all(|e| e.compressed_len > 0 && e.compressed_len < e.original_len));
+        assert!(truncated.is_err());
+    }
+
+    #[test]
+    fn test_decompress_refuses_overwrite() {
+        let dir = TempDir::new("force");
+        let input = dir.join("input");
+        let archive = dir.join("archive.lzav");
+        let output = dir.join("output");
+        fs::create_dir_all(input.join("sub")).unwrap();
+        fs::write(input.join("a.txt"), b"new contents ".repeat(10)).unwrap();
+        fs::write(input.join("sub/b.txt"), b"other new contents ".repeat(10)).unwrap();
+        fs::create_dir_all(output.join("sub")).unwrap();
+        fs::write(output.join("sub/b.txt"), b"existing").unwrap();
+
+        compress_folder(input.to_str().unwrap(), archive.to_str().unwrap(), CompressLevel::Default).unwrap();
+        let (archive, output_str) = (archive.to_str().unwrap(), output.to_str().unwrap());
+
+        let refused = decompress_archive(archive, output_str, ExtractOptions::default()).map_err(|e| e.to_string());
+        let untouched = (fs::read(output.join("sub/b.txt")).unwrap(), output.join("a.txt").exists());
+        let force = ExtractOptions { force: true, ..Default::default() };
+        decompress_archive(archive, output_str, force).unwrap();
+        let forced = fs::read(output.join("sub/b.txt")).unwrap();
+
+        assert!(refused.unwrap_err().contains("b.txt"));
+        assert_eq!(untouched, (b"existing".to_vec(), false));
+        assert_eq!(forced, b"other new contents ".repeat(10));
+    }
+
+    #[test]
+    fn test_level_recorded_per_entry() {
+        let dir = TempDir::new("level");
+        let input = dir.join("input.txt");
+        let archive = dir.join("archive.lzav");
+        let output = dir.join("output.txt");
+        let data: Vec<u8> = (0..2000u32)
+            .flat_map(|i| format!("leveled entry {} of {}, ", i % 97, i % 13).into_bytes())
+            .collect();
+        fs::write(&input, &data).unwrap();
+
+        let mut levels = Vec::new();
+        let mut sizes = Vec::new();
+        for level in [CompressLevel::Fast, CompressLevel::Default, CompressLevel::Max] {
+            compress_folder(input.to_str().unwrap(), archive.to_str().unwrap(), level).unwrap();
+            let entries = read_archive_index(archive.to_str().unwrap()).unwrap();
+            let force = ExtractOptions { force: true, ..Default::default() };
+            decompress_archive(archive.to_str().unwrap(), output.to_str().unwrap(), force).unwrap();
+            levels.push((entries[0].level, fs::read(&output).unwrap() == data));
+            sizes.push(entries[0].compressed_len);
+        }
+
+        // Each entry records the level that was applied, and it shows
+        let fast = applied_level(CompressLevel::Fast);
+        assert_eq!(levels, [(fast, true), (CompressLevel::Default, true), (CompressLevel::Max, true)]);
+        assert!(sizes[2] < sizes[1], "max should compress better: {:?}", sizes);
+        if fast == CompressLevel::Fast {
+            assert_ne!(sizes[0], sizes[1]);
+        } else {
+            assert_eq!(sizes[0], sizes[1]);
+        }
+        for name in ["fast", "default", "max"] {
+            assert_eq!(parse_level(name).map(level_name), Some(name));
+        }
+        assert_eq!(parse_level("best"), None);
+        assert_eq!(level_from_code(3), None);
+    }
+
+    #[test]
+    fn test_verify_archive() {
+        let dir = TempDir::new("verify");
+        let input = dir.join("input");
+        let archive = dir.join("archive.lzav");
+        fs::create_dir_all(input.join("sub")).unwrap();
+        fs::write(input.join("a.txt"), b"verified file ".repeat(100)).unwrap();
+        fs::write(input.join("sub/b.txt"), b"nested verified file ".repeat(50)).unwrap();
+        fs::write(input.join("sub/empty.txt"), b"").unwrap();
+        compress_folder(input.to_str().unwrap(), archive.to_str().unwrap(), CompressLevel::Default).unwrap();
+        let archive_str = archive.to_str().unwrap();
+
+        let clean = verify_archive(archive_str).unwrap();
+
+        // Misstate the first entry's length: it fails and the rest still pass
+        let mut bytes = fs::read(&archive).unwrap();
+        let len_at = ARCHIVE_HEADER_LEN + 5 + "a.txt".len();
+        bytes[len_at..len_at + 4].copy_from_slice(&1401u32.to_le_bytes());
+        fs::write(&archive, &bytes).unwrap();
+        let misstated = verify_archive(archive_str).unwrap();
+
+        fs::write(&archive, &bytes[..bytes.len() - 1]).unwrap();
+        let truncated = verify_archive(archive_str);
+        let mut listing: Vec<_> = fs::read_dir(&*dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        listing.sort();
+
+        let summary = |entries: &[VerifiedEntry]| -> Vec<_> {
+            entries.iter().map(|e| (e.path.clone(), e.result.clone())).collect()
+        };
+        assert_eq!(summary(&clean), [
+            ("a.txt".to_string(), Ok(1400)),
+            ("sub/b.txt".to_string(), Ok(1050)),
+            ("sub/empty.txt".to_string(), Ok(0)),
+        ]);
+        assert_eq!(summary(&misstated[..1]), [
+            ("a.txt".to_string(), Err("decoded 1400 bytes, expected 1401".to_string())),
+        ]);
+        assert!(misstated[1..].iter().all(|e| e.result.is_ok()));
+        assert!(truncated.is_err());
+        assert_eq!(listing, ["archive.lzav", "input"]);
+    }
+
+    #[test]
+    fn test_cat_entry_streams_one_file() {
+        let dir = TempDir::new("cat");
+        let input = dir.join("input");
+        let archive = dir.join("archive.lzav");
+        fs::create_dir_all(input.join("sub")).unwrap();
+        let big: Vec<u8> = (0..BUFFER_SIZE * 2 + 123).map(|i| (i % 251) as u8 ^ (i >> 12) as u8).collect();
+        fs::write(input.join("a.bin"), &big).unwrap();
+        fs::write(input.join("sub/b.txt"), b"streamed to a writer ".repeat(30)).unwrap();
+        compress_folder(input.to_str().unwrap(), archive.to_str().unwrap(), CompressLevel::Default).unwrap();
+        let archive_str = archive.to_str().unwrap();
+
+        let mut first = Vec::new();
+        let first_len = cat_entry(archive_str, "a.bin", &mut first).unwrap();
+        let mut second = Vec::new();
+        let second_len = cat_entry(archive_str, "sub/b.txt", &mut second).unwrap();
+        let missing = cat_entry(archive_str, "nope", &mut Vec::new());
+
+        assert_eq!(first_len, big.len() as u64);
+        assert!(first == big);
+        assert_eq!(second_len, 630);
+        assert_eq!(second, b"streamed to a writer ".repeat(30));
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_cli_errors_name_the_entry() {
+        let dir = TempDir::new("errors");
+        let input = dir.join("input");
+        let archive = dir.join("archive.lzav");
+        fs::create_dir_all(input.join("sub")).unwrap();
+        fs::write(input.join("a.txt"), b"diagnosed file ".repeat(100)).unwrap();
+        fs::write(input.join("sub/b.txt"), b"second diagnosed file ".repeat(40)).unwrap();
+        compress_folder(input.to_str().unwrap(), archive.to_str().unwrap(), CompressLevel::Default).unwrap();
+        let archive_str = archive.to_str().unwrap();
+        let bytes = fs::read(&archive).unwrap();
+
+        // a.txt's header after the archive's: path length, level, path,
+        // original length, chunk count, then its chunk's original and
+        // compressed lengths
+        let original_at = ARCHIVE_HEADER_LEN + 5 + "a.txt".len();
+        let chunk_len_at = original_at + 8;
+        let with = |at: usize, value: u32| {
+            let mut edited = bytes.clone();
+            edited[at..at + 4].copy_from_slice(&value.to_le_bytes());
+            fs::write(&archive, &edited).unwrap();
+        };
+        let cli_error = |result: Result<u64, Box<dyn std::error::Error>>| {
+            *result.unwrap_err().downcast::<CliError>().unwrap()
+        };
+
+        with(original_at, 1501);
+        let misstated = cli_error(cat_entry(archive_str, "a.txt", &mut Vec::new()));
+        with(chunk_len_at, 1499);
+        let short_chunk = cli_error(cat_entry(archive_str, "a.txt", &mut Vec::new()));
+        with(chunk_len_at, 0);
+        let empty_chunk = cli_error(cat_entry(archive_str, "a.txt", &mut Vec::new()));
+        with(ARCHIVE_HEADER_LEN, u32::MAX);
+        let bad_path = cli_error(cat_entry(archive_str, "a.txt", &mut Vec::new()));
+        let mut edited = bytes.clone();
+        edited[ARCHIVE_HEADER_LEN + 4] = 3;
+        fs::write(&archive, &edited).unwrap();
+        let bad_level = cli_error(cat_entry(archive_str, "a.txt", &mut Vec::new()));
+        fs::write(&archive, &bytes[..bytes.len() - 1]).unwrap();
+        let truncated = read_archive_index(archive_str).err().unwrap().downcast::<CliError>().unwrap();
+
+        // Archives without the header, as written before it existed, and
+        // later versions are refused up front
+        fs::write(&archive, &bytes[ARCHIVE_HEADER_LEN..]).unwrap();
+        let headerless = read_archive_index(archive_str).err().unwrap().downcast::<CliError>().unwrap();
+        with(4, ARCHIVE_VERSION + 1);
+        let future = cli_error(cat_entry(archive_str, "a.txt", &mut Vec::new()));
+
+        assert_eq!(misstated, CliError::LengthMismatch { path: "a.txt".into(), expected: 1501, decoded: 1500 });
+        assert!(
+            matches!(&short_chunk, CliError::Decompress { path, chunk: 0, .. } if path == "a.txt"),
+            "{short_chunk:?}"
+        );
+        assert!(std::error::Error::source(&short_chunk).unwrap().is::<LzavError>());
+        assert_eq!(empty_chunk, CliError::InvalidChunkHeader { path: "a.txt".into(), chunk: 0 });
+        assert_eq!(bad_path, CliError::BadPathLength { len: u32::MAX });
+        assert_eq!(bad_level, CliError::UnknownLevel { code: 3 });
+        assert_eq!(*truncated, CliError::ArchiveTruncated { path: "sub/b.txt".into() });
+        assert_eq!(truncated.to_string(), "Archive truncated in sub/b.txt");
+        assert_eq!(*headerless, CliError::UnsupportedArchive { version: None });
+        assert!(headerless.to_string().starts_with("Unsupported archive version"));
+        assert_eq!(future, CliError::UnsupportedArchive { version: Some(ARCHIVE_VERSION + 1) });
+    }
+
+    #[test]
+    fn test_extract_path_rejects_traversal() {
+        let output = Path::new("out");
+        for path in ["../escape.txt", "/etc/passwd", "a/../../b", "./a"] {
+            assert!(extract_path(output, true, path).is_err(), "{}", path);
+        }
+        assert_eq!(extract_path(output, true, "a/b.txt").unwrap(), output.join("a/b.txt"));
+        assert_eq!(extract_path(Path::new("f.txt"), false, "../x").unwrap(), Path::new("f.txt"));
+    }
+
+    #[test]
+    fn test_decompress_target_kind() {
+        let dir = TempDir::new("target");
+        let input = dir.join("input");
+        fs::create_dir_all(&input).unwrap();
+        fs::write(input.join("a.txt"), b"first entry ".repeat(10)).unwrap();
+        fs::write(input.join("b.txt"), b"second entry ".repeat(10)).unwrap();
+        let single = dir.join("single.lzav");
+        let multi = dir.join("multi.lzav");
+        compress_folder(input.join("a.txt").to_str().unwrap(), single.to_str().unwrap(), CompressLevel::Default).unwrap();
+        compress_folder(input.to_str().unwrap(), multi.to_str().unwrap(), CompressLevel::Default).unwrap();
+        let (single, multi) = (single.to_str().unwrap(), multi.to_str().unwrap());
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        let options = ExtractOptions::default();
+        let to_dir = ExtractOptions { to_dir: true, ..Default::default() };
+
+        // Names with extensions no longer decide the target kind
+        decompress_archive(multi, &path("data.2024"), options).unwrap();
+        decompress_archive(single, &path("my.backup"), options).unwrap();
+        fs::create_dir(dir.join("existing.d")).unwrap();
+        decompress_archive(single, &path("existing.d"), options).unwrap();
+        decompress_archive(single, &path("folder"), to_dir).unwrap();
+        fs::write(dir.join("plain.txt"), b"a file").unwrap();
+        let multi_into_file = decompress_archive(multi, &path("plain.txt"), options);
+
+        let results = (
+            dir.join("data.2024/b.txt").is_file(),
+            dir.join("my.backup").is_file(),
+            dir.join("existing.d/a.txt").is_file(),
+            dir.join("folder/a.txt").is_file(),
+            fs::read(dir.join("plain.txt")).unwrap(),
+        );
+        assert_eq!(results, (true, true, true, true, b"a file".to_vec()));
+        assert!(multi_into_file.is_err());
+    }
+
+    #[test]
+    fn test_stats_totals_and_json() {
+        let dir = TempDir::new("stats");
+        let input = dir.join("input");
+        let archive = dir.join("archive.lzav");
+        fs::create_dir_all(&input).unwrap();
+        fs::write(input.join("a.txt"), b"measured contents ".repeat(100)).unwrap();
+        fs::write(input.join("b.txt"), b"more measured contents ".repeat(50)).unwrap();
+
+        let compressed = compress_folder(input.to_str().unwrap(), archive.to_str().unwrap(), CompressLevel::Default).unwrap();
+        let output = dir.join("output");
+        let decompressed =
+            decompress_archive(archive.to_str().unwrap(), output.to_str().unwrap(), ExtractOptions::default())
+                .unwrap();
+
+        assert_eq!(compressed.original, 1800 + 1150);
+        assert!(compressed.compressed > 0 && compressed.compressed < compressed.original);
+        assert_eq!(decompressed, compressed);
+
+        let totals = Totals { original: 2_000_000, compressed: 500_000 };
+        assert_eq!(
+            stats_json("compress", totals, Duration::from_millis(500)),
+            r#"{"operation":"compress","original_bytes":2000000,"compressed_bytes":500000,"ratio":0.2500,"elapsed_secs":0.500000,"mb_per_sec":4.00}"#
+        );
+    }
+}
@@ -0,0 +1,101 @@
+// C ABI exports of the Rust backend. Build a shared library with
+// `cargo rustc --lib --release --no-default-features --features capi,std --crate-type cdylib`
+
+use core::slice;
+
+use super::{compress_bound, compress_default, decompress};
+use crate::errors::LZAV_E_PARAMS;
+
+/// Compress `srclen` bytes at `src` into `dst`, returning the compressed
+/// length or a negative `LZAV_E_*` code.
+///
+/// # Safety
+/// `src` must be valid for `srclen` reads and `dst` for `dstlen` writes.
+#[no_mangle]
+pub unsafe extern "C" fn rlzav_compress(
+    src: *const u8,
+    srclen: i32,
+    dst: *mut u8,
+    dstlen: i32,
+) -> i32 {
+    if src.is_null() || dst.is_null() || srclen < 0 || dstlen < 0 {
+        return LZAV_E_PARAMS;
+    }
+
+    let src = slice::from_raw_parts(src, srclen as usize);
+    let dst = slice::from_raw_parts_mut(dst, dstlen as usize);
+    compress_default(src, dst)
+}
+
+/// Decompress `srclen` bytes at `src` into `dst`, returning the decompressed
+/// length or a negative `LZAV_E_*` code.
+///
+/// # Safety
+/// `src` must be valid for `srclen` reads and `dst` for `dstlen` writes.
+#[no_mangle]
+pub unsafe extern "C" fn rlzav_decompress(
+    src: *const u8,
+    srclen: i32,
+    dst: *mut u8,
+    dstlen: i32,
+) -> i32 {
+    if src.is_null() || dst.is_null() || srclen < 0 || dstlen < 0 {
+        return LZAV_E_PARAMS;
+    }
+
+    let src = slice::from_raw_parts(src, srclen as usize);
+    let dst = slice::from_raw_parts_mut(dst, dstlen as usize);
+    decompress(src, dst)
+}
+
+/// Buffer size `rlzav_compress` needs for `srclen` input bytes.
+#[no_mangle]
+pub extern "C" fn rlzav_compress_bound(srclen: i32) -> i32 {
+    compress_bound(srclen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    #[test]
+    fn test_capi_roundtrip() {
+        let original = b"C ABI roundtrip, C ABI roundtrip, C ABI roundtrip";
+        let mut compressed = vec![0u8; rlzav_compress_bound(original.len() as i32) as usize];
+        let mut decompressed = vec![0u8; original.len()];
+
+        unsafe {
+            let compressed_len = rlzav_compress(
+                original.as_ptr(),
+                original.len() as i32,
+                compressed.as_mut_ptr(),
+                compressed.len() as i32,
+            );
+            assert!(compressed_len > 0);
+
+            let decompressed_len = rlzav_decompress(
+                compressed.as_ptr(),
+                compressed_len,
+                decompressed.as_mut_ptr(),
+                decompressed.len() as i32,
+            );
+            assert_eq!(decompressed_len, original.len() as i32);
+        }
+        assert_eq!(decompressed, original);
+    }
+
+    #[test]
+    fn test_capi_rejects_bad_params() {
+        let mut buf = [0u8; 64];
+        let data = b"data";
+
+        unsafe {
+            assert_eq!(rlzav_compress(ptr::null(), 4, buf.as_mut_ptr(), 64), LZAV_E_PARAMS);
+            assert_eq!(rlzav_compress(data.as_ptr(), 4, ptr::null_mut(), 64), LZAV_E_PARAMS);
+            assert_eq!(rlzav_compress(data.as_ptr(), -1, buf.as_mut_ptr(), 64), LZAV_E_PARAMS);
+            assert_eq!(rlzav_decompress(data.as_ptr(), 4, buf.as_mut_ptr(), -1), LZAV_E_PARAMS);
+            assert_eq!(rlzav_decompress(ptr::null(), 4, buf.as_mut_ptr(), 64), LZAV_E_PARAMS);
+        }
+    }
+}
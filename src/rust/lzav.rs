@@ -1,25 +1,80 @@
-use std::collections::HashMap;
-use std::alloc::{Layout, alloc_zeroed, dealloc};
+use alloc::vec::Vec;
+use core::mem::MaybeUninit;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+
+use crate::checksum::crc32;
+use crate::errors::LzavError;
+use crate::sink::Sink;
+use crate::utils::copy_overlapping;
+use crate::CompressLevel;
 
-// Ensure cache line alignment
-const CACHE_LINE: usize = 64;
 const WINDOW_SIZE: usize = 8 * 1024 * 1024;
-const MIN_MATCH_LENGTH: usize = 4;
-const MAX_MATCH_LENGTH: usize = 258;
-const HASH_BITS: u32 = 16;
+// Smallest encoder window, still wider than the longest run `find_run` emits;
+// `find_match` skips period multiples that would reach past the window
+const MIN_WINDOW_SIZE: usize = 1024;
+const MIN_MATCH_LENGTH: usize = LZAV_REF_MIN;
+const MAX_MATCH_LENGTH: usize = LZAV_REF_LEN;
+// Shortest minimum match `set_min_match_length` accepts; hashes cover 4 bytes
+const MIN_MATCH_LENGTH_FLOOR: usize = 4;
+
+// LZAV stream format 2 parameters, shared with the C backend
+use crate::scan::{
+    is_tiny_stream, read_literal_len, read_literal_len_1, LZAV_LIT_FIN, LZAV_REF_LEN, LZAV_REF_MIN,
+    LZAV_TINY_MAX,
+};
+use crate::{LZAV_FMT_CUR, LZAV_FMT_MIN};
+// Shortest run of one byte ahead of the parse that `find_run` encodes
+const RLE_MIN_RUN: usize = 32;
 
 // Add optimized hash table constants
 const HASH_L1_BITS: u32 = 12;  // 4KB hash table fits in L1 cache
 const HASH_L2_BITS: u32 = 15;  // 32KB for larger inputs
 const HASH_L3_BITS: u32 = 17;  // 128KB for maximum compression
 
-#[derive(Debug, Clone)]  // Add Clone to fix move issues
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]  // Add Clone to fix move issues
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CompressedData {
     pub metadata: FileMetadata,
+    #[cfg_attr(feature = "serde", serde(with = "serde_bytes"))]
     pub data: Vec<u8>,
 }
 
-#[derive(Debug, Clone, Copy)]  // Add Copy to allow dereferencing
+impl CompressedData {
+    /// Wrap a raw LZAV stream with the length and CRC-32 of the data it
+    /// holds, such as values kept alongside it in a database row.
+    pub fn from_bytes(data: Vec<u8>, original_size: u32, checksum: u32) -> Self {
+        Self { metadata: FileMetadata { original_size, checksum }, data }
+    }
+
+    /// Parse a frame written by `compress_frame`, taking its length and
+    /// checksum from the header. Frames over `LZAV_WIN_LEN` bytes, which are
+    /// split into blocks, and frames with a checksum other than CRC-32 are
+    /// `UnknownFormat`; the body is only decoded by `decompress`.
+    pub fn decode(bytes: &[u8]) -> Result<Self, LzavError> {
+        let (data, original_len, checksum) = crate::frame::frame_stream(bytes)?;
+        let original_size = u32::try_from(original_len).map_err(|_| LzavError::DestOutOfBounds)?;
+        Ok(Self::from_bytes(data, original_size, checksum))
+    }
+
+    /// Length of the compressed stream in bytes.
+    pub fn compressed_len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Length of the data before compression.
+    pub fn original_len(&self) -> usize {
+        self.metadata.original_size as usize
+    }
+
+    /// The compressed stream.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]  // Add Copy to allow dereferencing
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileMetadata {
     pub original_size: u32,  // Changed from usize to u32 to reduce size
     pub checksum: u32,
@@ -46,6 +101,12 @@ impl Swar {
         Self { data }
     }
 
+    /// Every byte set to `byte`.
+    #[inline(always)]
+    fn splat(byte: u8) -> Self {
+        Self { data: u64::from_le_bytes([byte; 8]) }
+    }
+
     #[inline(always)]
     fn find_match_length(&self, other: &Self) -> usize {
         let xor = self.data ^ other.data;
@@ -53,493 +114,2404 @@ impl Swar {
             return 8;
         }
         
-        // Find first differing byte using trailing zeros of byte-wise comparison
+        // Find first differing byte using trailing zeros of byte-wise comparison.
+        // `from_bytes` and `splat` load little-endian on every target, so byte
+        // `i` is always bits `8 * i..8 * i + 8` and this holds on big-endian too
         let byte_diff = xor.trailing_zeros() / 8;
         byte_diff as usize
     }
+}
 
-    #[inline(always)]
-    fn to_bytes(&self) -> [u8; 8] {
-        self.data.to_le_bytes()
-    }
+type MatchLenFn = fn(&[u8], &[u8], usize) -> usize;
+
+// Runtime CPU detection needs `std`; without it only compile-time target
+// features are used
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+macro_rules! has_x86_feature {
+    ($f:tt) => { std::is_x86_feature_detected!($f) };
 }
+#[cfg(all(not(feature = "std"), target_arch = "x86_64"))]
+macro_rules! has_x86_feature {
+    ($f:tt) => { cfg!(target_feature = $f) };
+}
+#[cfg(all(feature = "std", target_arch = "aarch64", target_endian = "little"))]
+macro_rules! has_aarch64_feature {
+    ($f:tt) => { core::arch::is_aarch64_feature_detected!($f) };
+}
+#[cfg(all(not(feature = "std"), target_arch = "aarch64", target_endian = "little"))]
+macro_rules! has_aarch64_feature {
+    ($f:tt) => { cfg!(target_feature = $f) };
+}
+
+// Widest match length routine the running CPU supports, picked on first use
+#[cfg(feature = "std")]
+static MATCH_LEN: OnceLock<MatchLenFn> = OnceLock::new();
 
-#[repr(align(64))]  // Cache line alignment without packing
-struct HashTable {
-    buckets: *mut Vec<usize>,
-    size: u32,  // Changed from usize to u32 to reduce size
+/// Number of equal leading bytes of `a` and `b`, at most `limit`. Both slices
+/// must hold at least `limit` bytes.
+#[inline(always)]
+fn match_len(a: &[u8], b: &[u8], limit: usize) -> usize {
+    #[cfg(feature = "std")]
+    let f = *MATCH_LEN.get_or_init(select_match_len);
+    #[cfg(not(feature = "std"))]
+    let f = select_match_len();
+    f(a, b, limit)
 }
 
-impl HashTable {
-    fn new(size: usize) -> Self {
-        unsafe {
-            let layout = Layout::from_size_align(
-                size * std::mem::size_of::<Vec<usize>>(),
-                CACHE_LINE
-            ).unwrap();
-            let ptr = alloc_zeroed(layout) as *mut Vec<usize>;
-            // Initialize each bucket
-            for i in 0..size {
-                ptr.add(i).write(Vec::new());
-            }
-            Self { buckets: ptr, size: size as u32 }
+fn select_match_len() -> MatchLenFn {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_x86_feature!("avx2") {
+            return match_len_avx2;
+        }
+        if has_x86_feature!("sse2") {
+            return match_len_sse2;
+        }
+    }
+    #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+    {
+        if has_aarch64_feature!("neon") {
+            return match_len_neon;
         }
     }
+    match_len_swar
+}
 
-    #[inline(always)]
-    unsafe fn get_bucket(&self, hash: u32) -> &mut Vec<usize> {
-        &mut *self.buckets.add(hash as usize % self.size as usize)
+/// Portable fallback comparing eight bytes at a time.
+fn match_len_swar(a: &[u8], b: &[u8], limit: usize) -> usize {
+    let mut len = 0;
+
+    // Compare eight bytes at a time, then finish byte-wise
+    while len + 8 <= limit {
+        let a_swar = Swar::from_bytes(&a[len..]);
+        let b_swar = Swar::from_bytes(&b[len..]);
+        let matched = a_swar.find_match_length(&b_swar);
+        len += matched;
+        if matched < 8 {
+            return len;
+        }
     }
+
+    while len < limit && a[len] == b[len] {
+        len += 1;
+    }
+    len
+}
+
+#[cfg(target_arch = "x86_64")]
+fn match_len_sse2(a: &[u8], b: &[u8], limit: usize) -> usize {
+    // SAFETY: only selected after SSE2 support was detected
+    unsafe { match_len_sse2_impl(&a[..limit], &b[..limit]) }
 }
 
-impl Drop for HashTable {
-    fn drop(&mut self) {
-        unsafe {
-            let layout = Layout::from_size_align(
-                self.size as usize * std::mem::size_of::<Vec<usize>>(),
-                CACHE_LINE
-            ).unwrap();
-            dealloc(self.buckets as *mut u8, layout);
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+unsafe fn match_len_sse2_impl(a: &[u8], b: &[u8]) -> usize {
+    use core::arch::x86_64::*;
+
+    let mut len = 0;
+    while len + 16 <= a.len() {
+        let va = _mm_loadu_si128(a.as_ptr().add(len) as *const __m128i);
+        let vb = _mm_loadu_si128(b.as_ptr().add(len) as *const __m128i);
+        let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(va, vb)) as u32;
+        if mask != 0xFFFF {
+            return len + (!mask).trailing_zeros() as usize;
         }
+        len += 16;
     }
+    len + match_len_swar(&a[len..], &b[len..], a.len() - len)
 }
 
-pub struct SWARCompressor {
-    hash_table: HashMap<u32, Vec<usize>>,
+#[cfg(target_arch = "x86_64")]
+fn match_len_avx2(a: &[u8], b: &[u8], limit: usize) -> usize {
+    // SAFETY: only selected after AVX2 support was detected
+    unsafe { match_len_avx2_impl(&a[..limit], &b[..limit]) }
 }
 
-impl SWARCompressor {
-    pub fn new() -> Self {
-        Self {
-            hash_table: HashMap::with_capacity(1 << HASH_BITS),
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn match_len_avx2_impl(a: &[u8], b: &[u8]) -> usize {
+    use core::arch::x86_64::*;
+
+    let mut len = 0;
+    while len + 32 <= a.len() {
+        let va = _mm256_loadu_si256(a.as_ptr().add(len) as *const __m256i);
+        let vb = _mm256_loadu_si256(b.as_ptr().add(len) as *const __m256i);
+        let mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(va, vb)) as u32;
+        if mask != u32::MAX {
+            return len + (!mask).trailing_zeros() as usize;
         }
+        len += 32;
     }
+    len + match_len_sse2_impl(&a[len..], &b[len..])
+}
 
-    #[inline(always)]
-    fn get_hash_bits(&self, input_size: usize) -> u32 {
-        // Calculate optimal hash table size based on input size
-        if (input_size <= (16 << 10)) {      // <= 16KB
-            HASH_L1_BITS
-        } else if (input_size <= (128 << 10)) { // <= 128KB
-            HASH_L2_BITS
-        } else {
-            HASH_L3_BITS
+#[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+fn match_len_neon(a: &[u8], b: &[u8], limit: usize) -> usize {
+    // SAFETY: only selected after NEON support was detected
+    unsafe { match_len_neon_impl(&a[..limit], &b[..limit]) }
+}
+
+#[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+#[target_feature(enable = "neon")]
+unsafe fn match_len_neon_impl(a: &[u8], b: &[u8]) -> usize {
+    use core::arch::aarch64::*;
+
+    let mut len = 0;
+    while len + 16 <= a.len() {
+        let va = vld1q_u8(a.as_ptr().add(len));
+        let vb = vld1q_u8(b.as_ptr().add(len));
+        let eq = vreinterpretq_u64_u8(vceqq_u8(va, vb));
+
+        // Equal bytes compare to 0xFF; the first zero byte is the mismatch
+        let lo = vgetq_lane_u64::<0>(eq);
+        if lo != u64::MAX {
+            return len + ((!lo).trailing_zeros() / 8) as usize;
+        }
+        let hi = vgetq_lane_u64::<1>(eq);
+        if hi != u64::MAX {
+            return len + 8 + ((!hi).trailing_zeros() / 8) as usize;
         }
+        len += 16;
+    }
+    len + match_len_swar(&a[len..], &b[len..], a.len() - len)
+}
+
+/// Output buffer for an LZAV format 2 stream, tracking where the offset
+/// carry bits of the latest reference block go.
+struct Fmt2Writer {
+    out: Vec<u8>,
+    carry_pos: usize,
+    carry_shift: u32,
+    mref: usize, // Minimum reference length, recorded in the prefix byte
+}
+
+impl Fmt2Writer {
+    fn new(out: Vec<u8>, capacity: usize) -> Self {
+        Self::with_mref(out, capacity, LZAV_REF_MIN)
+    }
+
+    fn with_mref(mut out: Vec<u8>, capacity: usize, mref: usize) -> Self {
+        out.clear();
+        out.reserve(capacity + capacity / 64 + 16);
+        out.push(LZAV_FMT_CUR << 4 | mref as u8); // prefix byte
+        Self { out, carry_pos: 0, carry_shift: 0, mref }
     }
 
     #[inline(always)]
-    fn hash(&self, data: &[u8], pos: usize) -> u32 {
-        if pos + 4 > data.len() {
-            return 0;
+    fn write_literal_header(&mut self, cv: u8, lc: usize) {
+        if lc < 16 {
+            self.out.push(cv | lc as u8);
+            return;
         }
-        
-        // Improved hashing using komihash-style mixing
-        let mut h = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
-        let seed1 = 0x243F6A88 ^ h;
-        let mut seed2 = 0x85A308D3;
-        
-        if pos + 6 <= data.len() {
-            seed2 ^= u16::from_le_bytes(data[pos + 4..pos + 6].try_into().unwrap()) as u32;
+
+        self.out.push(cv);
+        let mut lcw = lc - 16;
+        while lcw > 127 {
+            self.out.push(0x80 | lcw as u8);
+            lcw >>= 7;
         }
-        
-        let hm = (seed1 as u64).wrapping_mul(seed2 as u64);
-        let hval = (hm as u32) ^ ((hm >> 32) as u32);
-        
-        hval & ((1 << self.get_hash_bits(data.len())) - 1)
+        self.out.push(lcw as u8);
     }
 
-    #[inline(always)]
-    fn compare_bytes_swar(&self, a: &[u8], b: &[u8], len: usize) -> bool {
-        let chunks = len / 8;
-        let remainder = len % 8;
+    /// Write pending literals followed by a reference of `rc` bytes at
+    /// distance `d`. Requires `mref <= rc <= d`.
+    fn write_block(&mut self, literals: &[u8], rc: usize, mut d: usize) {
+        // A zero distance would decode as LZAV_E_REFOOB
+        debug_assert!((1..WINDOW_SIZE).contains(&d), "reference distance {d} out of range");
+        debug_assert!((self.mref..=d).contains(&rc), "reference length {rc} at distance {d}");
+        let rc = rc + 1 - self.mref;
 
-        for i in 0..chunks {
-            let a_swar = Swar::from_bytes(&a[i * 8..]);
-            let b_swar = Swar::from_bytes(&b[i * 8..]);
-            if a_swar.data != b_swar.data {
-                return false;
-            }
+        // Low offset bits are carried into the previous reference block
+        if self.carry_shift != 0 {
+            self.out[self.carry_pos] |= ((d << 8) >> self.carry_shift) as u8;
+            d >>= self.carry_shift;
         }
 
-        if remainder > 0 {
-            let start = chunks * 8;
-            let a_remainder = &a[start..start + remainder];
-            let b_remainder = &b[start..start + remainder];
-            a_remainder == b_remainder
-        } else {
-            true
+        if !literals.is_empty() {
+            let cv = ((d & 3) << 6) as u8;
+            d >>= 2;
+            self.write_literal_header(cv, literals.len());
+            self.out.extend_from_slice(literals);
         }
-    }
 
-    #[inline(always)]
-    fn find_match(&self, data: &[u8], pos: usize, hash: u32) -> Option<(u32, u16)> {
-        let positions = self.hash_table.get(&hash)?;
-        
-        // Prefetch next hash bucket
-        #[cfg(target_arch = "x86_64")]
-        if pos + 4 <= data.len() {
-            let next_hash = self.hash(data, pos + 1);
-            if let Some(next_positions) = self.hash_table.get(&next_hash) {
-                unsafe {
-                    use std::arch::x86_64::_mm_prefetch;
-                    _mm_prefetch::<3>(next_positions.as_ptr() as *const i8);
-                }
-            }
+        // `d` is below the 8 MiB window, so `d << 6` needs at most 29 bits and
+        // the header fits in the `bt + 1 <= 4` bytes written
+        let bt = 1 + (d > (1 << 10) - 1) as usize + (d > (1 << 18) - 1) as usize;
+        let mut header = (d << 6 | bt << 4) as u32;
+        if rc < 16 {
+            header |= rc as u32;
         }
+        self.out.extend_from_slice(&header.to_le_bytes()[..=bt]);
+        self.carry_pos = self.out.len() - 1;
+        self.carry_shift = if bt == 3 { 3 } else { 0 };
 
-        let mut best_len = MIN_MATCH_LENGTH - 1;
-        let mut best_dist = 0;
+        if rc >= 16 + 255 {
+            self.out.push(255);
+            self.out.push((rc - 16 - 255) as u8);
+        } else if rc >= 16 {
+            self.out.push((rc - 16) as u8);
+        }
+    }
 
-        if pos + 8 <= data.len() {
-            let current_swar = Swar::from_bytes(&data[pos..]);
+    /// Store inputs of up to `LZAV_TINY_MAX` bytes as a single raw block,
+    /// like the C backend.
+    fn tiny(out: Vec<u8>, data: &[u8]) -> Vec<u8> {
+        let mut writer = Self::new(out, data.len());
+        writer.out.push(data.len() as u8);
+        writer.out.extend_from_slice(data);
+        writer.out
+    }
 
-            for &prev_pos in positions.iter().rev() {
-                let distance = pos - prev_pos;
-                if distance >= WINDOW_SIZE {
-                    break;
-                }
+    fn write_final(&mut self, literals: &[u8]) {
+        self.write_literal_header(0, literals.len());
+        self.out.extend_from_slice(literals);
+    }
+}
 
-                let prev_swar = Swar::from_bytes(&data[prev_pos..]);
-                let match_len = current_swar.find_match_length(&prev_swar);
+/// View an initialized buffer as the possibly uninitialized buffer the
+/// decoder writes to.
+#[inline(always)]
+fn as_uninit(dst: &mut [u8]) -> &mut [MaybeUninit<u8>] {
+    // SAFETY: same layout, and the decoder only ever stores initialized bytes
+    unsafe { &mut *(dst as *mut [u8] as *mut [MaybeUninit<u8>]) }
+}
 
-                if match_len > best_len {
-                    best_len = match_len;
-                    best_dist = distance as u32;
+/// Copy a `cc` byte literal run from `src[ip..]` to `dst[op..]`. On failure
+/// returns the bytes that could still be written along with the error.
+#[inline(always)]
+fn copy_literal(
+    src: &[u8],
+    ip: usize,
+    dst: &mut [MaybeUninit<u8>],
+    op: usize,
+    cc: usize,
+) -> Result<(), (usize, LzavError)> {
+    let avail = cc.min(src.len() - ip).min(dst.len() - op);
+    dst[op..op + avail].write_copy_of_slice(&src[ip..ip + avail]);
+    if avail < cc {
+        let err = if cc > src.len() - ip {
+            LzavError::SourceOutOfBounds
+        } else {
+            LzavError::DestOutOfBounds
+        };
+        return Err((avail, err));
+    }
+    Ok(())
+}
 
-                    if match_len == 8 {
-                        let mut total_len = 8;
-                        let mut curr_pos = pos + 8;
-                        let mut prev_pos = prev_pos + 8;
-
-                        // Use SWAR for bulk comparison
-                        while curr_pos + 8 <= data.len() && 
-                              total_len < MAX_MATCH_LENGTH && 
-                              self.compare_bytes_swar(
-                                  &data[prev_pos..prev_pos + 8],
-                                  &data[curr_pos..curr_pos + 8],
-                                  8
-                              ) {
-                            total_len += 8;
-                            curr_pos += 8;
-                            prev_pos += 8;
-                        }
+/// Copy `cc` bytes from `d` bytes back in `dst` to `dst[op..]`, with the same
+/// failure convention as `copy_literal`. Only reads the already written
+/// `dst[..op + cc]`, so `dst` may start out uninitialized.
+#[inline(always)]
+fn copy_reference(
+    dst: &mut [MaybeUninit<u8>],
+    op: usize,
+    d: usize,
+    cc: usize,
+) -> Result<(), (usize, LzavError)> {
+    if d == 0 || d > op {
+        return Err((0, LzavError::ReferenceOutOfBounds));
+    }
 
-                        // Handle remaining bytes
-                        while curr_pos < data.len() && 
-                              total_len < MAX_MATCH_LENGTH && 
-                              data[prev_pos] == data[curr_pos] {
-                            total_len += 1;
-                            curr_pos += 1;
-                            prev_pos += 1;
-                        }
+    let avail = cc.min(dst.len() - op);
+    copy_overlapping(dst, op, op - d, avail);
+    if avail < cc {
+        return Err((avail, LzavError::DestOutOfBounds));
+    }
+    Ok(())
+}
 
-                        return Some((best_dist, total_len as u16));
-                    }
-                }
-            }
-        }
+/// Decode an LZAV stream into `dst`, stopping at the first error or once
+/// `dst` is full. Returns the bytes of `src` taken by fully decoded blocks,
+/// the bytes written to `dst` and the decoding status.
+fn decode_stream(src: &[u8], dst: &mut [MaybeUninit<u8>]) -> (usize, usize, Result<(), LzavError>) {
+    decode_window(src, dst, 0)
+}
 
-        if best_len >= MIN_MATCH_LENGTH {
-            Some((best_dist, best_len as u16))
-        } else {
-            None
+/// Like `decode_stream`, but decodes into `dst[start..]` after a preset
+/// dictionary in the initialized `dst[..start]`, which references may reach
+/// back into. The bytes written do not count the dictionary.
+fn decode_window(
+    src: &[u8],
+    dst: &mut [MaybeUninit<u8>],
+    start: usize,
+) -> (usize, usize, Result<(), LzavError>) {
+    // An empty input compresses to an empty stream
+    if src.is_empty() {
+        return (0, 0, Ok(()));
+    }
+    if src.len() < 2 {
+        return (0, 0, Err(LzavError::SourceOutOfBounds));
+    }
+    if let Some(payload) = crate::stored_payload(src) {
+        let n = payload.len().min(dst.len() - start);
+        dst[start..start + n].write_copy_of_slice(&payload[..n]);
+        if n < payload.len() {
+            return (0, n, Err(LzavError::DestOutOfBounds));
         }
+        return (src.len(), n, Ok(()));
+    }
+    let mref = (src[0] & 15) as usize;
+    if mref == 0 {
+        return (0, 0, Err(LzavError::UnknownFormat));
     }
 
-    #[inline(always)]
-    fn compare_bytes_simd(&self, a: &[u8], b: &[u8], len: usize) -> bool {
-        #[cfg(target_arch = "x86_64")]
-        unsafe {
-            use std::arch::x86_64::*;
-            let chunks = len / 16;
-            let remainder = len % 16;
-
-            for i in 0..chunks {
-                let a_vec = _mm_loadu_si128(a[i * 16..].as_ptr() as *const __m128i);
-                let b_vec = _mm_loadu_si128(b[i * 16..].as_ptr() as *const __m128i);
-                if _mm_movemask_epi8(_mm_cmpeq_epi8(a_vec, b_vec)) != 0xFFFF {
-                    return false;
-                }
-            }
+    let (consumed, op, status) = match src[0] >> 4 {
+        LZAV_FMT_CUR => decode_fmt2(src, dst, mref, start),
+        LZAV_FMT_MIN => decode_fmt1(src, dst, mref, start),
+        _ => return (0, 0, Err(LzavError::UnknownFormat)),
+    };
+    // A complete stream ends with a literal block reaching its last byte, so
+    // bytes left over mean it was cut short inside a block
+    let status = status.and(if consumed == src.len() { Ok(()) } else { Err(LzavError::SourceOutOfBounds) });
+    (consumed, op - start, status)
+}
 
-            if remainder > 0 {
-                let start = chunks * 16;
-                a[start..start + remainder] == b[start..start + remainder]
-            } else {
-                true
-            }
-        }
-        #[cfg(not(target_arch = "x86_64"))]
-        self.compare_bytes_swar(a, b, len)
+/// Decode the prior-generation format 1 stream, which has no varint lengths
+/// and carries offset bits through literal and type 3 block headers. Such
+/// streams are rare, so it stays out of line rather than inflating every
+/// decompress entry point.
+#[cold]
+#[inline(never)]
+fn decode_fmt1(
+    src: &[u8],
+    dst: &mut [MaybeUninit<u8>],
+    mref: usize,
+    mut op: usize,
+) -> (usize, usize, Result<(), LzavError>) {
+    let ipet = src.len().saturating_sub(5); // Block header read threshold
+    let mut ip = 1;
+    let mut cv = 0usize; // Reference offset carry value
+    let mut csh = 0u32; // Reference offset carry shift
+
+    if ip >= ipet {
+        return (0, op, Err(LzavError::SourceOutOfBounds));
     }
 
-    pub fn compress(&mut self, data: &[u8]) -> CompressedData {
-        let mut compressed = Vec::with_capacity(data.len());
-        let mut pos = 0;
-        let mut literals = Vec::new();
-        let mut mavg: i64 = 100 << 21; // Running average of match rate
-        
-        // Setup optimized hash table size
-        self.hash_table = HashMap::with_capacity(
-            1 << self.get_hash_bits(data.len())
-        );
+    while ip < ipet {
+        let consumed = ip;
+        let bh = src[ip] as usize;
+        ip += 1;
 
-        while pos < data.len() {
-            // Early exit for small remaining data
-            if pos + MIN_MATCH_LENGTH > data.len() {
-                literals.push(data[pos]);
-                pos += 1;
-                continue;
+        if bh & 0x30 == 0 {
+            // Literal block, always resets the carry
+            cv = bh >> 6;
+            csh = 2;
+
+            let mut cc = bh & 15;
+            if cc == 0 {
+                let Some((extra, read)) = read_literal_len_1(&src[ip..]) else {
+                    return (consumed, op, Err(LzavError::SourceOutOfBounds));
+                };
+                cc = extra + 16;
+                ip += read;
             }
 
-            let hash = self.hash(data, pos);
-            if let Some((distance, length)) = self.find_match(data, pos, hash) {
-                // Update match rate average
-                mavg += ((length as i64) << 21) - (mavg >> 10);
+            if let Err((written, err)) = copy_literal(src, ip, dst, op, cc) {
+                return (consumed, op + written, Err(err));
+            }
+            ip += cc;
+            op += cc;
+        } else {
+            // Reference block; type 3 leaves its header bits as carry
+            let bt = (bh >> 4) & 3;
+            let Some(offset) = src.get(ip..ip + bt) else {
+                return (consumed, op, Err(LzavError::SourceOutOfBounds));
+            };
+            let o = offset.iter().rev().fold(0usize, |acc, &b| acc << 8 | b as usize);
+            ip += bt;
 
-                // Handle literals if any
-                if !literals.is_empty() {
-                    self.write_literals(&mut compressed, &literals);
-                    literals.clear();
-                }
+            let d = if bt == 3 {
+                let d = (o << csh) | cv;
+                cv = bh >> 6;
+                csh = 2;
+                d
+            } else {
+                let d = ((bh >> 6 | o << 2) << csh) | cv;
+                cv = 0;
+                csh = 0;
+                d
+            };
 
-                // Write match reference
-                self.write_match(&mut compressed, distance, length);
+            let mut cc = bh & 15;
+            if cc == 0 {
+                let Some(&lb) = src.get(ip) else {
+                    return (consumed, op, Err(LzavError::SourceOutOfBounds));
+                };
+                ip += 1;
+                cc = 16 + lb as usize;
+            }
+            cc += mref - 1;
 
-                // Update hash table entries
-                for i in 0..length as usize {
-                    if pos + i + 4 <= data.len() {
-                        let h = self.hash(data, pos + i);
-                        self.hash_table.entry(h)
-                            .or_insert_with(Vec::new)
-                            .push(pos + i);
-                    }
-                }
-                pos += length as usize;
-            } else {
-                // Compression speed-up for poor match rates
-                if mavg < (200 << 14) {
-                    let skip = if mavg < (130 << 14) {
-                        if mavg < (100 << 14) {
-                            // Very poor matching, skip aggressively
-                            3
-                        } else {
-                            2
-                        }
-                    } else {
-                        1
-                    };
-                    
-                    // Add skipped bytes to literals
-                    literals.extend_from_slice(&data[pos..pos + skip.min(data.len() - pos)]);
-                    pos += skip;
-                } else {
-                    literals.push(data[pos]);
-                    self.hash_table.entry(hash)
-                        .or_insert_with(Vec::new)
-                        .push(pos);
-                    pos += 1;
-                }
+            if let Err((written, err)) = copy_reference(dst, op, d, cc) {
+                return (consumed, op + written, Err(err));
             }
+            op += cc;
         }
+    }
 
-        // Handle remaining literals
-        if !literals.is_empty() {
-            self.write_literals(&mut compressed, &literals);
-        }
+    (ip, op, Ok(()))
+}
 
-        CompressedData {
-            metadata: FileMetadata {
-                original_size: data.len() as u32,
-                checksum: self.calculate_checksum(data),
-            },
-            data: compressed,
+/// Decode the body of a format 2 stream, including the tiny stream layout.
+/// Output starts at `dst[op..]`; the returned position includes `op`.
+fn decode_fmt2(
+    src: &[u8],
+    dst: &mut [MaybeUninit<u8>],
+    mref: usize,
+    op: usize,
+) -> (usize, usize, Result<(), LzavError>) {
+    if is_tiny_stream(src) {
+        let len = src[1] as usize;
+        let n = len.min(dst.len() - op);
+        dst[op..op + n].write_copy_of_slice(&src[2..2 + n]);
+        if n < len {
+            return (0, op + n, Err(LzavError::DestOutOfBounds));
         }
+        return (src.len(), op + n, Ok(()));
     }
 
-    #[inline(always)]
-    fn write_literals(&self, compressed: &mut Vec<u8>, literals: &[u8]) {
-        compressed.push(0);
-        compressed.extend_from_slice(&(literals.len() as u16).to_le_bytes());
-        compressed.extend_from_slice(literals);
+    let ipet = src.len().saturating_sub(LZAV_LIT_FIN); // Block header read threshold
+    if 1 >= ipet {
+        return (0, op, Err(LzavError::SourceOutOfBounds));
     }
 
-    #[inline(always)]
-    fn write_match(&self, compressed: &mut Vec<u8>, distance: u32, length: u16) {
-        compressed.push(1);
-        compressed.extend_from_slice(&distance.to_le_bytes());
-        compressed.extend_from_slice(&length.to_le_bytes());
-    }
-
-    pub fn decompress(&self, compressed: &CompressedData) -> Vec<u8> {
-        let mut result = Vec::with_capacity(compressed.metadata.original_size as usize);
-        let mut pos = 0;
-        let data = &compressed.data; // Local copy to avoid packed field access
-
-        while pos < data.len() {
-            match data[pos] {
-                0 => {
-                    let len = u16::from_le_bytes(
-                        data[pos + 1..pos + 3].try_into().unwrap()
-                    ) as usize;
-                    result.extend_from_slice(&data[pos + 3..pos + 3 + len]);
-                    pos += 3 + len;
-                }
-                1 => {
-                    let distance = u32::from_le_bytes(
-                        data[pos + 1..pos + 5].try_into().unwrap()
-                    ) as usize;
-                    let length = u16::from_le_bytes(
-                        data[pos + 5..pos + 7].try_into().unwrap()
-                    ) as usize;
-                    
-                    let start = result.len() - distance;
-                    // Handle backreferences byte by byte to ensure correct order
-                    for i in 0..length {
-                        result.push(result[start + i]);
-                    }
-                    pos += 7;
-                }
-                _ => panic!("Invalid token type"),
-            }
-        }
+    let stop = decode_fmt2_blocks(src, 1, dst, op, mref, Fmt2Carry::default());
+    (stop.ip, stop.op + stop.partial, stop.status)
+}
 
-        assert_eq!(result.len() as u32, compressed.metadata.original_size);
-        
-        // Calculate checksum on the fully decompressed data
-        let decompressed_checksum = self.calculate_checksum(&result);
-        assert_eq!(decompressed_checksum, compressed.metadata.checksum);
-        
-        result
+/// Offset carry bits a format 2 decoder passes from block to block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Fmt2Carry {
+    cv: usize,  // Reference offset carry value
+    csh: u32,   // Reference offset carry shift
+}
+
+/// Where `decode_fmt2_blocks` stopped: `ip`, `op` and `carry` are at the
+/// start of the block that failed (or at the end), `partial` counts the
+/// bytes that block wrote past `op` before failing.
+struct Fmt2Stop {
+    ip: usize,
+    op: usize,
+    carry: Fmt2Carry,
+    partial: usize,
+    status: Result<(), LzavError>,
+}
+
+impl Fmt2Stop {
+    #[cold]
+    fn failed(ip: usize, op: usize, carry: Fmt2Carry, partial: usize, err: LzavError) -> Self {
+        Self { ip, op, carry, partial, status: Err(err) }
     }
+}
 
-    pub fn decompress_size(&self, data: &[u8]) -> usize {
-        let mut pos = 0;
-        let mut total_size = 0;
-        
-        while pos < data.len() {
-            match data[pos] {
-                0 => {
-                    if pos + 3 > data.len() { break; }
-                    let len = u16::from_le_bytes(data[pos + 1..pos + 3].try_into().unwrap()) as usize;
-                    total_size += len;
-                    pos += 3 + len;
-                }
-                1 => {
-                    if pos + 7 > data.len() { break; }
-                    let length = u16::from_le_bytes(data[pos + 5..pos + 7].try_into().unwrap()) as usize;
-                    total_size += length;
-                    pos += 7;
+/// Decode format 2 blocks from `src[ip..]` into `dst[op..]` until a block
+/// header would fall in the final `LZAV_LIT_FIN` bytes of `src`, a block
+/// does not fit in `src` or `dst`, or the data is invalid. The carry only
+/// changes once a block is complete, so after a stop it matches `ip`.
+#[inline(always)]
+fn decode_fmt2_blocks(
+    src: &[u8],
+    mut ip: usize,
+    dst: &mut [MaybeUninit<u8>],
+    mut op: usize,
+    mref: usize,
+    mut carry: Fmt2Carry,
+) -> Fmt2Stop {
+    let ipet = src.len().saturating_sub(LZAV_LIT_FIN); // Block header read threshold
+    while ip < ipet {
+        let consumed = ip;
+        let bh = src[ip] as usize;
+        ip += 1;
+
+        if bh & 0x30 == 0 {
+            // Literal block
+            let mut cc = bh & 15;
+            if cc == 0 {
+                let Some((extra, read)) = read_literal_len(&src[ip..]) else {
+                    return Fmt2Stop::failed(consumed, op, carry, 0, LzavError::SourceOutOfBounds);
+                };
+                // Saturates where a 32-bit usize cannot hold the length
+                cc = extra.saturating_add(16);
+                ip += read;
+            }
+
+            if let Err((written, err)) = copy_literal(src, ip, dst, op, cc) {
+                return Fmt2Stop::failed(consumed, op, carry, written, err);
+            }
+            carry.cv |= (bh >> 6).checked_shl(carry.csh).unwrap_or(0);
+            carry.csh = carry.csh.saturating_add(2);
+            ip += cc;
+            op += cc;
+        } else {
+            // Reference block with a 1, 2 or 3 byte offset field
+            let bt = (bh >> 4) & 3;
+            let Some(offset) = src.get(ip..ip + bt) else {
+                return Fmt2Stop::failed(consumed, op, carry, 0, LzavError::SourceOutOfBounds);
+            };
+            let o = offset.iter().rev().fold(0usize, |acc, &b| acc << 8 | b as usize);
+            ip += bt;
+
+            let d = ((bh >> 6) | (o & 0x1FFFFF) << 2).checked_shl(carry.csh).unwrap_or(usize::MAX) | carry.cv;
+
+            let mut cc = bh & 15;
+            if cc == 0 {
+                let Some(&lb) = src.get(ip) else {
+                    return Fmt2Stop::failed(consumed, op, carry, 0, LzavError::SourceOutOfBounds);
+                };
+                ip += 1;
+                cc = 16 + lb as usize;
+                if lb == 255 {
+                    let Some(&lb2) = src.get(ip) else {
+                        return Fmt2Stop::failed(consumed, op, carry, 0, LzavError::SourceOutOfBounds);
+                    };
+                    ip += 1;
+                    cc += lb2 as usize;
                 }
-                _ => break,
             }
+            cc += mref - 1;
+
+            if let Err((written, err)) = copy_reference(dst, op, d, cc) {
+                return Fmt2Stop::failed(consumed, op, carry, written, err);
+            }
+            carry.csh = if bt == 3 { 3 } else { 0 };
+            carry.cv = o >> 21;
+            op += cc;
         }
-        total_size
     }
 
-    pub fn calculate_initial_checksum(&self, data: &[u8]) -> u32 {
-        let mut result = Vec::new();
-        let mut pos = 0;
+    Fmt2Stop { ip, op, carry, partial: 0, status: Ok(()) }
+}
 
-        // Decompress data to calculate checksum
-        while pos < data.len() {
-            match data[pos] {
-                0 => {
-                    if pos + 3 > data.len() { break; }
-                    let len = u16::from_le_bytes(data[pos + 1..pos + 3].try_into().unwrap()) as usize;
-                    if pos + 3 + len > data.len() { break; }
-                    result.extend_from_slice(&data[pos + 3..pos + 3 + len]);
-                    pos += 3 + len;
-                }
-                1 => {
-                    if pos + 7 > data.len() { break; }
-                    let distance = u32::from_le_bytes(data[pos + 1..pos + 5].try_into().unwrap()) as usize;
-                    let length = u16::from_le_bytes(data[pos + 5..pos + 7].try_into().unwrap()) as usize;
-                    
-                    let start = result.len() - distance;
-                    for i in 0..length {
-                        result.push(result[start + i]);
-                    }
-                    pos += 7;
-                }
-                _ => break,
+/// Progress of a decode split over several `decompress_resume` calls. Start
+/// each stream with `ResumeState::default()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResumeState {
+    written: usize, // Output so far; decoding resumes at `dst[written..]`
+    mref: usize,    // Minimum reference length, 0 until the prefix byte is read
+    stored: bool,   // The stream is a `compress_or_store` copy
+    carry: Fmt2Carry,
+}
+
+impl ResumeState {
+    /// Bytes decoded by all calls so far.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+}
+
+/// Continue decoding `src`, the part of the stream not consumed yet, into
+/// `dst[state.written()..]`, stopping only between blocks. Returns the bytes
+/// of `src` consumed and of `dst` written by this call.
+pub(crate) fn decode_resume(src: &[u8], dst: &mut [u8], state: &mut ResumeState) -> Result<(usize, usize), LzavError> {
+    let dst = as_uninit(dst);
+    if dst.len() < state.written {
+        return Err(LzavError::InvalidParams);
+    }
+    let room = dst.len() - state.written;
+
+    let mut ip = 0;
+    if state.mref == 0 && !state.stored {
+        let Some(&prefix) = src.first() else {
+            return Ok((0, 0));
+        };
+        if prefix == crate::LZAV_STORED {
+            state.stored = true;
+        } else if prefix >> 4 != LZAV_FMT_CUR || prefix & 15 == 0 {
+            return Err(LzavError::UnknownFormat);
+        } else if is_tiny_stream(src) {
+            // A single block, so it is decoded whole or not at all
+            let len = src[1] as usize;
+            if len > room {
+                return Ok((0, 0));
             }
+            dst[state.written..state.written + len].write_copy_of_slice(&src[2..]);
+            state.written += len;
+            state.stored = true; // Nothing follows, and an empty rest is a no-op
+            return Ok((src.len(), len));
+        } else {
+            state.mref = (prefix & 15) as usize;
         }
+        ip = 1;
+    }
 
-        self.calculate_checksum(&result)
+    if state.stored {
+        let n = (src.len() - ip).min(room);
+        dst[state.written..state.written + n].write_copy_of_slice(&src[ip..ip + n]);
+        state.written += n;
+        return Ok((ip + n, n));
     }
 
-    #[inline(always)]
-    fn calculate_checksum(&self, data: &[u8]) -> u32 {
-        let mut checksum = 0u32;
-        for &byte in data {
-            checksum = checksum.wrapping_add(byte as u32);
-            checksum = checksum.rotate_left(1);
-        }
-        checksum
+    let stop = decode_fmt2_blocks(src, ip, dst, state.written, state.mref, state.carry);
+    state.carry = stop.carry;
+    match stop.status {
+        Ok(()) | Err(LzavError::SourceOutOfBounds | LzavError::DestOutOfBounds) => {}
+        Err(e) => return Err(e),
     }
+    let n = stop.op - state.written;
+    state.written = stop.op;
+    Ok((stop.ip, n))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_empty_input() {
-        let data = b"";
-        let mut compressor = SWARCompressor::new();
-        let compressed = compressor.compress(data);
-        let decompressed = compressor.decompress(&compressed);
-        assert_eq!(decompressed, data);
+/// Distance and length of a reference continuing a run of one byte at `pos`,
+/// when at least `RLE_MIN_RUN` copies of `data[pos]` lie ahead and the run
+/// already covers `MIN_MATCH_LENGTH` bytes behind `pos`. The reference
+/// copies the run behind `pos`, so it never overlaps its own output, and
+/// successive calls double its length up to `max_len`.
+#[inline(always)]
+fn find_run(data: &[u8], pos: usize, end: usize, max_len: usize) -> Option<(usize, usize)> {
+    if pos < MIN_MATCH_LENGTH || pos + RLE_MIN_RUN > end {
+        return None;
     }
 
-    #[test]
-    fn test_compression_decompression() {
-        let data = b"This is a test string with some repetition. \
-                    This is a test string with some repetition. \
-                    This is a test string with some repetition. \
-                    This is a test string with some repetition.";
-        
-        let mut compressor = SWARCompressor::new();
-        let compressed = compressor.compress(data);
-        let compressed_len = compressed.data.len(); // Store length locally
-        
-        println!("Original size: {}, Compressed size: {}", data.len(), compressed_len);
-        assert!(compressed_len < data.len());
-        
-        let decompressed = compressor.decompress(&compressed);
-        assert_eq!(decompressed, data);
+    let byte = Swar::splat(data[pos]);
+    let ahead = (pos..pos + RLE_MIN_RUN)
+        .step_by(8)
+        .all(|p| Swar::from_bytes(&data[p..]).find_match_length(&byte) == 8);
+    if !ahead {
+        return None;
     }
 
-    #[test]
-    fn test_large_repetitive_input() {
-        let data: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
-        let mut compressor = SWARCompressor::new();
-        let compressed = compressor.compress(&data);
-        
-        println!("Original size: {}, Compressed size: {}", data.len(), compressed.data.len());
-        assert!(compressed.data.len() < data.len());
-        
-        let decompressed = compressor.decompress(&compressed);
-        assert_eq!(decompressed, data);
+    // Inside a long run the whole span behind `pos` matches itself shifted
+    // by one byte, which match_len checks a vector at a time
+    let span = pos.min(max_len);
+    let start = pos - span;
+    let whole_span = data[pos - 1] == data[pos]
+        && match_len(&data[start..], &data[start + 1..], span - 1) == span - 1;
+    let back = if whole_span {
+        span
+    } else {
+        data[start..pos].iter().rev().take_while(|&&b| b == data[pos]).count()
+    };
+    if back < MIN_MATCH_LENGTH {
+        return None;
     }
 
-    #[test]
-    fn test_highly_repetitive() {
-        let mut data = Vec::with_capacity(1000);
-        for _ in 0..100 {
-            data.extend_from_slice(b"HelloWorld");
+    // The run ahead may continue past RLE_MIN_RUN, up to the distance and
+    // short of the final literals
+    let length = match_len(&data[pos..], &data[pos - back..], back.min(end - pos));
+    Some((back, length))
+}
+
+// Marks an empty hash chain slot; stored positions are offset by one
+const CHAIN_END: u32 = 0;
+
+// Starting value of the encoder's running match rate average
+const MAVG_INIT: i64 = 100 << 21;
+
+/// How the last input was parsed into literals and references, from
+/// [`SWARCompressor::stats`] or [`compress_stats`](crate::rust::compress_stats).
+/// All zero for empty input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressStats {
+    /// References written, including runs of one byte.
+    pub matches: usize,
+    /// Bytes covered by those references.
+    pub match_bytes: usize,
+    /// Uninterrupted runs of literal bytes, the final one included.
+    pub literal_runs: usize,
+    /// Bytes written as literals.
+    pub literal_bytes: usize,
+    /// Running match rate average when the parse ended, in the encoder's
+    /// fixed point; below `130 << 14` the encoder skips ahead on misses.
+    pub mavg: i64,
+}
+
+impl CompressStats {
+    /// Mean length of the references written, 0.0 when there are none.
+    pub fn average_match_length(&self) -> f64 {
+        self.match_bytes as f64 / self.matches.max(1) as f64
+    }
+
+    #[inline(always)]
+    fn record_literals(&mut self, len: usize) {
+        if len > 0 {
+            self.literal_runs += 1;
+            self.literal_bytes += len;
+        }
+    }
+
+    #[inline(always)]
+    fn record_match(&mut self, literals: usize, length: usize) {
+        self.record_literals(literals);
+        self.matches += 1;
+        self.match_bytes += length;
+    }
+}
+
+/// Hash chain heads and links, borrowed from the compressor's own tables or
+/// from a caller's scratch buffer.
+struct MatchIndex<'a> {
+    head: &'a mut [u32],
+    prev: &'a mut [u32],
+}
+
+impl MatchIndex<'_> {
+    #[inline(always)]
+    fn insert(&mut self, hash: u32, pos: usize) {
+        debug_assert!((hash as usize) < self.head.len(), "hash {hash} outside the head table");
+        let slot = pos & (self.prev.len() - 1);
+        self.prev[slot] = self.head[hash as usize];
+        self.head[hash as usize] = pos as u32 + 1;
+    }
+}
+
+// Match search effort for a `CompressLevel`
+#[derive(Debug, Clone, Copy)]
+struct SearchParams {
+    max_chain: usize, // Hash chain candidates examined per position
+    nice_len: usize,  // Match length that ends the search early
+    skip: bool,       // Skip ahead on poor match rates
+    lazy: bool,       // Prefer a longer match starting one byte later
+}
+
+impl SearchParams {
+    fn for_level(level: CompressLevel) -> Self {
+        match level {
+            CompressLevel::Fast => Self { max_chain: 4, nice_len: 8, skip: true, lazy: false },
+            CompressLevel::Default => Self { max_chain: 256, nice_len: 8, skip: true, lazy: false },
+            CompressLevel::Max => Self {
+                max_chain: 1024,
+                nice_len: MAX_MATCH_LENGTH,
+                skip: false,
+                lazy: true,
+            },
+        }
+    }
+}
+
+/// LZAV format 2 compressor that keeps its match tables between calls.
+/// It is `Send` and `Sync`; see `CompressorPool` to share several of them
+/// between threads.
+pub struct SWARCompressor {
+    head: Vec<u32>, // Latest position + 1 for each hash bucket
+    prev: Vec<u32>, // Previous position + 1 with the same hash, per position
+    out: Vec<u8>,   // Scratch output reused by `compress_with`
+    search: SearchParams,
+    window: usize,    // Largest reference distance plus one, a power of two
+    min_match: usize, // Shortest reference written, the stream's `mref`
+    verify_checksum: bool,
+    stats: CompressStats, // Parse of the last non-empty input
+}
+
+// Keep the thread-safety promised above from regressing silently
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SWARCompressor>();
+};
+
+impl Default for SWARCompressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SWARCompressor {
+    pub fn new() -> Self {
+        Self::with_level(CompressLevel::Default)
+    }
+
+    /// Create a compressor that searches for matches with `level` effort.
+    /// The level only changes the parse; every level writes the same format.
+    pub fn with_level(level: CompressLevel) -> Self {
+        Self {
+            head: Vec::new(), // Sized per input by `compress`
+            prev: Vec::new(),
+            out: Vec::new(),
+            search: SearchParams::for_level(level),
+            window: WINDOW_SIZE,
+            min_match: MIN_MATCH_LENGTH,
+            verify_checksum: true,
+            stats: CompressStats::default(),
+        }
+    }
+
+    /// Limit how many earlier positions with the same hash are compared per
+    /// match search. Higher values find longer matches at a speed cost.
+    pub fn set_max_chain(&mut self, max_chain: usize) {
+        self.search.max_chain = max_chain.max(1);
+    }
+
+    /// Limit references to `window_size - 1` bytes back, shrinking the chain
+    /// table and hash heads to match. `window_size` must be a power of two
+    /// from 1 KiB to `LZAV_WIN_LEN`, otherwise `InvalidParams` is returned and
+    /// the window is unchanged. Streams stay readable by any decoder.
+    pub fn set_window_size(&mut self, window_size: usize) -> Result<(), LzavError> {
+        if !window_size.is_power_of_two() || !(MIN_WINDOW_SIZE..=WINDOW_SIZE).contains(&window_size) {
+            return Err(LzavError::InvalidParams);
+        }
+        self.window = window_size;
+        Ok(())
+    }
+
+    /// Emit references as short as `min_match` bytes, from 4 to the default
+    /// of 6, otherwise `InvalidParams` is returned and the setting is
+    /// unchanged. Shorter matches help small records with few long repeats,
+    /// but each one only saves a byte or two over literals, so larger inputs
+    /// usually compress better and faster at the default. The length is
+    /// recorded in the stream, which any decoder reads.
+    pub fn set_min_match_length(&mut self, min_match: usize) -> Result<(), LzavError> {
+        if !(MIN_MATCH_LENGTH_FLOOR..=MIN_MATCH_LENGTH).contains(&min_match) {
+            return Err(LzavError::InvalidParams);
+        }
+        self.min_match = min_match;
+        Ok(())
+    }
+
+    // Longest reference the stream's length fields hold at `min_match`
+    #[inline(always)]
+    fn max_match(&self) -> usize {
+        MAX_MATCH_LENGTH - (MIN_MATCH_LENGTH - self.min_match)
+    }
+
+    /// Statistics of the parse behind the last non-empty input this
+    /// compressor wrote. Gathering them does not change the output.
+    pub fn stats(&self) -> CompressStats {
+        self.stats
+    }
+
+    /// Choose whether `decompress` compares the output against the stored
+    /// checksum. Disabling it skips only that pass: references are still
+    /// bounds-checked and the output length is still verified.
+    pub fn set_verify_checksum(&mut self, verify: bool) {
+        self.verify_checksum = verify;
+    }
+
+    /// Clear the match index without releasing its memory.
+    pub fn reset(&mut self) {
+        self.head.fill(CHAIN_END);
+        self.prev.clear();
+        self.out.clear();
+    }
+
+    /// Compress `src` into `dst` reusing this compressor's buffers, returning
+    /// the compressed length or a negative `LZAV_E_*` code; `LZAV_E_DSTOOB`
+    /// when the stream does not fit in `dst`. Empty input writes nothing.
+    pub fn compress_with(&mut self, src: &[u8], dst: &mut [u8]) -> i32 {
+        if src.is_empty() {
+            return 0;
+        }
+        if dst.is_empty() {
+            return LzavError::InvalidParams.code();
+        }
+
+        match self.compress_into(src, dst) {
+            Ok(len) => len as i32,
+            Err(e) => e.code(),
+        }
+    }
+
+    /// Compress `src` and push the stream to `sink` in one piece, returning
+    /// its length; a sink that fails receives nothing. Empty input writes
+    /// nothing. The format 2 writer patches offset bits back into earlier
+    /// block headers, so the stream is completed in the compressor's buffer
+    /// before it is pushed.
+    pub fn compress_into<S: Sink>(&mut self, src: &[u8], mut sink: S) -> Result<usize, S::Error> {
+        if src.is_empty() {
+            return Ok(0);
+        }
+
+        let out = core::mem::take(&mut self.out);
+        self.out = self.compress_stream(src, out, &mut { usize::MAX });
+        sink.push_bytes(&self.out)?;
+        Ok(self.out.len())
+    }
+
+    /// Bytes of scratch space `compress_with_scratch` needs for `src_len`
+    /// input bytes: the hash heads plus a chain link per window position.
+    pub fn scratch_size(&self, src_len: usize) -> usize {
+        let (head_len, prev_len) = self.table_sizes(src_len);
+        (head_len + prev_len) * core::mem::size_of::<u32>()
+    }
+
+    /// Like `compress_with`, but keeps the match index in `scratch`. Falls
+    /// back to the compressor's own tables when `scratch`, once aligned to
+    /// four bytes, is smaller than `scratch_size(src.len())`.
+    pub fn compress_with_scratch(&mut self, src: &[u8], dst: &mut [u8], scratch: &mut [u8]) -> i32 {
+        if src.is_empty() {
+            return 0;
+        }
+        if dst.is_empty() {
+            return LzavError::InvalidParams.code();
+        }
+
+        let (head_len, prev_len) = self.table_sizes(src.len());
+        // SAFETY: every bit pattern is a valid u32
+        let (_, words, _) = unsafe { scratch.align_to_mut::<u32>() };
+        if src.len() <= LZAV_TINY_MAX || words.len() < head_len + prev_len {
+            return self.compress_with(src, dst);
+        }
+
+        let (head, rest) = words.split_at_mut(head_len);
+        let prev = &mut rest[..prev_len];
+        head.fill(CHAIN_END);
+        prev.fill(CHAIN_END);
+
+        let out = core::mem::take(&mut self.out);
+        self.out = self.encode(&mut MatchIndex { head, prev }, src, 0, out, &mut { usize::MAX });
+        self.copy_out(dst)
+    }
+
+    /// Like `compress_with`, with `dict` treated as data just before `src`, so
+    /// references may point into it. Only the stream for `src` is written;
+    /// `decompress_with_dict` with the same dictionary restores it. At most
+    /// the last window (8 MiB by default) of `dict` is used, and inputs of up to 32 bytes are
+    /// stored without it.
+    pub fn compress_with_dict(&mut self, src: &[u8], dst: &mut [u8], dict: &[u8]) -> i32 {
+        if src.is_empty() {
+            return 0;
+        }
+        if dst.is_empty() {
+            return LzavError::InvalidParams.code();
+        }
+
+        let dict = &dict[dict.len().saturating_sub(self.window)..];
+        let out = core::mem::take(&mut self.out);
+        self.out = if dict.is_empty() || src.len() <= LZAV_TINY_MAX {
+            self.compress_stream(src, out, &mut { usize::MAX })
+        } else {
+            self.compress_window(&[dict, src].concat(), dict.len(), out, &mut { usize::MAX })
+        };
+        self.copy_out(dst)
+    }
+
+    /// Like `compress_with`, but examines at most `max_match_attempts` match
+    /// candidates over the whole call. Once they are used up the rest of
+    /// `src` is written as literals, so the work is bounded whatever the
+    /// input; the stream stays valid and only the ratio suffers.
+    pub fn compress_with_budget(&mut self, src: &[u8], dst: &mut [u8], max_match_attempts: usize) -> i32 {
+        if src.is_empty() {
+            return 0;
+        }
+        if dst.is_empty() {
+            return LzavError::InvalidParams.code();
+        }
+
+        let mut budget = max_match_attempts;
+        let out = core::mem::take(&mut self.out);
+        self.out = self.compress_stream(src, out, &mut budget);
+        self.copy_out(dst)
+    }
+
+    // Copy the stream in `self.out` to `dst`, returning its length or an error code
+    fn copy_out(&self, mut dst: &mut [u8]) -> i32 {
+        match dst.push_bytes(&self.out) {
+            Ok(()) => self.out.len() as i32,
+            Err(e) => e.code(),
+        }
+    }
+
+    // Hash head and chain link counts for an input of `src_len` bytes; the
+    // chain is a power-of-two ring that only covers positions in the window
+    #[inline(always)]
+    fn table_sizes(&self, src_len: usize) -> (usize, usize) {
+        (
+            1 << self.get_hash_bits(src_len),
+            src_len.clamp(1, self.window).next_power_of_two(),
+        )
+    }
+
+    #[inline(always)]
+    fn get_hash_bits(&self, input_size: usize) -> u32 {
+        // Calculate optimal hash table size based on input size, as no more
+        // than a window of it is ever searched
+        let input_size = input_size.min(self.window);
+        if input_size <= (16 << 10) {      // <= 16KB
+            HASH_L1_BITS
+        } else if input_size <= (128 << 10) { // <= 128KB
+            HASH_L2_BITS
+        } else {
+            HASH_L3_BITS
+        }
+    }
+
+    #[inline(always)]
+    /// Hash the 4 to 6 bytes at `pos` into a bucket of a `table_sizes`
+    /// head table. Callers only hash positions with 4 bytes left, so no
+    /// bucket is shared by a fallback for short tails. Below a 6 byte
+    /// minimum match only 4 bytes are hashed, so shorter matches still
+    /// share a bucket.
+    fn hash(&self, data: &[u8], pos: usize) -> u32 {
+        debug_assert!(pos + 4 <= data.len(), "hash at {pos} past the end of {} bytes", data.len());
+
+        // Improved hashing using komihash-style mixing
+        let h = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+        let seed1 = 0x243F6A88 ^ h;
+        let mut seed2 = 0x85A308D3;
+        
+        if self.min_match >= 6 && pos + 6 <= data.len() {
+            seed2 ^= u16::from_le_bytes(data[pos + 4..pos + 6].try_into().unwrap()) as u32;
+        }
+        
+        let hm = (seed1 as u64).wrapping_mul(seed2 as u64);
+        let hval = (hm as u32) ^ ((hm >> 32) as u32);
+        
+        hval & ((1 << self.get_hash_bits(data.len())) - 1)
+    }
+
+    #[inline(always)]
+    fn find_match(
+        &self,
+        index: &MatchIndex,
+        data: &[u8],
+        pos: usize,
+        hash: u32,
+        end: usize,
+        budget: &mut usize,
+    ) -> Option<(u32, u16)> {
+        let mut candidate = index.head[hash as usize];
+        if candidate == CHAIN_END {
+            return None;
+        }
+        
+        // Prefetch next hash bucket
+        #[cfg(target_arch = "x86_64")]
+        if pos + 5 <= data.len() {
+            let next_hash = self.hash(data, pos + 1);
+            unsafe {
+                use core::arch::x86_64::_mm_prefetch;
+                _mm_prefetch::<3>(index.head.as_ptr().add(next_hash as usize) as *const i8);
+            }
+        }
+
+        let mut best_len = self.min_match - 1;
+        let mut best_dist = 0;
+        let max_len = self.max_match().min(end - pos);
+
+        if pos + 8 <= data.len() {
+            // Bounded so inputs with long chains of short matches stay linear
+            let mut depth = self.search.max_chain;
+            while candidate != CHAIN_END && depth > 0 && *budget > 0 {
+                depth -= 1;
+                *budget -= 1;
+                let prev_pos = candidate as usize - 1;
+                candidate = index.prev[prev_pos & (index.prev.len() - 1)];
+
+                let mut distance = pos - prev_pos;
+                if distance >= self.window {
+                    break;
+                }
+
+                // fmt2 references may not overlap their own output, and the
+                // final LZAV_LIT_FIN bytes must stay literal. Capping at the
+                // distance also means a match always has `distance >= 1`
+                let mut len = match_len(&data[pos..], &data[prev_pos..], max_len.min(distance));
+
+                // A match reaching `pos` means the data repeats with period
+                // `distance`, so a multiple of it may cover a longer stretch
+                // without overlapping; this is what lets runs compress well.
+                // The multiple must stay inside the window like any reference
+                if len == distance && distance < max_len {
+                    let mut far = max_len.div_ceil(distance) * distance;
+                    if far > pos {
+                        far = pos - pos % distance;
+                    }
+                    if far > distance && far < self.window {
+                        let far_len = match_len(&data[pos..], &data[pos - far..], max_len.min(far));
+                        if far_len > len {
+                            len = far_len;
+                            distance = far;
+                        }
+                    }
+                }
+
+                if len > best_len {
+                    best_len = len;
+                    best_dist = distance as u32;
+
+                    if len >= self.search.nice_len {
+                        return Some((best_dist, best_len as u16));
+                    }
+                }
+            }
+        }
+
+        if best_len >= self.min_match {
+            Some((best_dist, best_len as u16))
+        } else {
+            None
+        }
+    }
+
+    /// Compress `data` into an LZAV format 2 stream, the same format the C
+    /// backend reads and writes.
+    pub fn compress(&mut self, data: &[u8]) -> CompressedData {
+        let metadata = FileMetadata {
+            original_size: data.len() as u32,
+            checksum: self.calculate_checksum(data),
+        };
+
+        if data.is_empty() {
+            return CompressedData { metadata, data: Vec::new() };
+        }
+
+        CompressedData {
+            metadata,
+            data: self.compress_stream(data, Vec::new(), &mut { usize::MAX }),
+        }
+    }
+
+    // Write the format 2 stream for non-empty `data` into `out`, examining at
+    // most `budget` match candidates, which is reduced by those examined;
+    // unlimited callers pass `&mut { usize::MAX }`
+    fn compress_stream(&mut self, data: &[u8], out: Vec<u8>, budget: &mut usize) -> Vec<u8> {
+        if data.len() <= LZAV_TINY_MAX {
+            self.stats = CompressStats {
+                literal_runs: 1,
+                literal_bytes: data.len(),
+                mavg: MAVG_INIT,
+                ..CompressStats::default()
+            };
+            return Fmt2Writer::tiny(out, data);
+        }
+        self.compress_window(data, 0, out, budget)
+    }
+
+    // Write the format 2 stream for `data[start..]`, longer than
+    // `LZAV_TINY_MAX`, after the dictionary `data[..start]`
+    fn compress_window(&mut self, data: &[u8], start: usize, out: Vec<u8>, budget: &mut usize) -> Vec<u8> {
+        // Setup optimized hash table size
+        let (head_len, prev_len) = self.table_sizes(data.len());
+        let mut head = core::mem::take(&mut self.head);
+        let mut prev = core::mem::take(&mut self.prev);
+        head.clear();
+        head.resize(head_len, CHAIN_END);
+        prev.clear();
+        prev.resize(prev_len, CHAIN_END);
+
+        let out = self.encode(&mut MatchIndex { head: &mut head, prev: &mut prev }, data, start, out, budget);
+        self.head = head;
+        self.prev = prev;
+        out
+    }
+
+    // Parse `data[start..]` (longer than `LZAV_TINY_MAX`) using a cleared
+    // `index`; `data[..start]` is a preset dictionary that is indexed first
+    // so references may point into it, but is not itself written. Once
+    // `budget` match candidates have been examined the rest is literals
+    fn encode(&mut self, index: &mut MatchIndex, data: &[u8], start: usize, out: Vec<u8>, budget: &mut usize) -> Vec<u8> {
+        let mut writer = Fmt2Writer::with_mref(out, data.len() - start, self.min_match);
+        let end = data.len() - LZAV_LIT_FIN;
+        let mut anchor = start;
+        let mut mavg = MAVG_INIT;
+        let mut stats = CompressStats::default();
+        #[cfg(feature = "log")]
+        let mut skipped = 0;
+        lzav_debug!(
+            "hash table of {} heads and {} chain links for {} bytes",
+            index.head.len(),
+            index.prev.len(),
+            data.len() - start
+        );
+
+        for p in 0..start {
+            if p + 4 <= data.len() {
+                index.insert(self.hash(data, p), p);
+            }
+        }
+        // A stream opening with a reference could read as a tiny stream, so
+        // with a dictionary the first byte is always a literal
+        let mut pos = if start > 0 { start + 1 } else { 0 };
+
+        while pos < end {
+            if *budget == 0 {
+                lzav_debug!("match budget exhausted at {}, {} bytes left as literals", pos, data.len() - pos);
+                break;
+            }
+
+            // Early exit for small remaining data
+            if pos + self.min_match > end {
+                pos += 1;
+                continue;
+            }
+
+            // Long runs of one byte continue the run behind `pos` without
+            // walking hash chains that are full of the same byte
+            if let Some((distance, length)) = find_run(data, pos, end, self.max_match()) {
+                mavg += ((length as i64) << 21) - (mavg >> 10);
+                stats.record_match(pos - anchor, length);
+                writer.write_block(&data[anchor..pos], length, distance);
+
+                // Only the run's tail is indexed, for matches that start in it
+                pos += length;
+                for p in pos - 8..pos {
+                    if p + 4 <= data.len() {
+                        index.insert(self.hash(data, p), p);
+                    }
+                }
+                anchor = pos;
+                continue;
+            }
+
+            let mut hash = self.hash(data, pos);
+            if let Some((mut distance, mut length)) = self.find_match(index, data, pos, hash, end, budget) {
+                // Positions below `indexed` are already in the hash chains
+                let mut indexed = pos;
+                if self.search.lazy {
+                    // Emit a literal instead while the next position has a longer
+                    // match; the literal costs a byte, so one byte longer is no gain
+                    while pos + 1 + self.min_match <= end {
+                        index.insert(hash, pos);
+                        indexed = pos + 1;
+
+                        let next_hash = self.hash(data, pos + 1);
+                        match self.find_match(index, data, pos + 1, next_hash, end, budget) {
+                            Some((d, l)) if l > length + 1 => {
+                                pos += 1;
+                                hash = next_hash;
+                                distance = d;
+                                length = l;
+                            }
+                            _ => break,
+                        }
+                    }
+                }
+
+                // Update match rate average
+                mavg += ((length as i64) << 21) - (mavg >> 10);
+                stats.record_match(pos - anchor, length as usize);
+
+                // Pending literals are written together with the reference
+                writer.write_block(&data[anchor..pos], length as usize, distance as usize);
+
+                // Update hash table entries
+                for p in indexed..pos + length as usize {
+                    if p + 4 <= data.len() {
+                        let h = self.hash(data, p);
+                        index.insert(h, p);
+                    }
+                }
+                pos += length as usize;
+                anchor = pos;
+            } else {
+                // Compression speed-up for poor match rates
+                if self.search.skip && mavg < (200 << 14) {
+                    let skip = if mavg < (130 << 14) {
+                        if mavg < (100 << 14) {
+                            // Very poor matching, skip aggressively
+                            3
+                        } else {
+                            2
+                        }
+                    } else {
+                        1
+                    };
+                    
+                    // Skipped bytes stay in the pending literal run
+                    lzav_trace!("skipping {} bytes at {} on match rate {}", skip, pos, mavg);
+                    #[cfg(feature = "log")]
+                    {
+                        skipped += skip;
+                    }
+                    pos += skip;
+                } else {
+                    index.insert(hash, pos);
+                    pos += 1;
+                }
+            }
+        }
+
+        // The stream always finishes with at least LZAV_LIT_FIN literals
+        writer.write_final(&data[anchor..]);
+        stats.record_literals(data.len() - anchor);
+        stats.mavg = mavg;
+        lzav_debug!(
+            "{} bytes to {}: {} matches of {:.1} bytes on average, {} literal bytes, {} bytes skipped, final match rate {}",
+            data.len() - start,
+            writer.out.len(),
+            stats.matches,
+            stats.average_match_length(),
+            stats.literal_bytes,
+            skipped,
+            mavg
+        );
+        self.stats = stats;
+        writer.out
+    }
+
+    /// Decompress `compressed`, verifying its length and, unless disabled by
+    /// `set_verify_checksum`, its checksum. Corrupt or truncated input is
+    /// reported as an error, never a panic.
+    pub fn decompress(&self, compressed: &CompressedData) -> Result<Vec<u8>, LzavError> {
+        let mut result = Vec::new();
+        self.decompress_to(compressed, &mut result)?;
+        Ok(result)
+    }
+
+    /// Like `decompress`, but decodes into `out`, replacing its contents and
+    /// reusing its capacity, so a loop over many frames allocates only when
+    /// a frame is larger than any before it. Returns the decompressed length;
+    /// on error `out` holds the bytes decoded before the failure.
+    pub fn decompress_to(&self, compressed: &CompressedData, out: &mut Vec<u8>) -> Result<usize, LzavError> {
+        let len = compressed.metadata.original_size as usize;
+        out.clear();
+        out.reserve(len);
+
+        if !compressed.data.is_empty() {
+            let (_, written, status) = decode_stream(&compressed.data, &mut out.spare_capacity_mut()[..len]);
+            // SAFETY: the decoder initialized the first `written` bytes
+            unsafe { out.set_len(written) };
+            status?;
+        }
+        if out.len() != len {
+            return Err(LzavError::Corrupt { at: out.len() });
+        }
+
+        // Calculate checksum on the fully decompressed data
+        if self.verify_checksum {
+            let (expected, actual) = (compressed.metadata.checksum, self.calculate_checksum(out));
+            if actual != expected {
+                return Err(LzavError::ChecksumMismatch { expected, actual });
+            }
+        }
+
+        Ok(len)
+    }
+
+    /// Decode as much of `src` as fits into `dst`, returning
+    /// `(src_consumed, dst_written)`. Only fully decoded blocks count towards
+    /// `src_consumed`; a block cut short by a full `dst` or a truncated `src`
+    /// still contributes the bytes it produced to `dst_written`.
+    pub fn decompress_partial(&self, src: &[u8], dst: &mut [u8]) -> (usize, usize) {
+        let (consumed, written, _) = decode_stream(src, as_uninit(dst));
+        (consumed, written)
+    }
+
+    /// Decode `src` straight into `dst` in a single pass, returning the
+    /// decompressed length.
+    pub fn decompress_into(&self, src: &[u8], dst: &mut [u8]) -> Result<usize, LzavError> {
+        self.decompress_into_uninit(src, as_uninit(dst))
+    }
+
+    /// Decode a stream written by `compress_with_dict` into `dst`, returning
+    /// the decompressed length. `dict` must be the dictionary it was
+    /// compressed with; any other fails or decodes different bytes.
+    ///
+    /// References reach from the output back into the dictionary, so each
+    /// call decodes into a temporary window of `dict.len() + dst.len()`
+    /// bytes, left unzeroed, and copies the output to `dst`. Only the last
+    /// `LZAV_WIN_LEN` bytes of `dict` are used, so small dictionaries keep
+    /// that cost small.
+    pub fn decompress_with_dict(&self, src: &[u8], dst: &mut [u8], dict: &[u8]) -> Result<usize, LzavError> {
+        let dict = &dict[dict.len().saturating_sub(WINDOW_SIZE)..];
+        let mut window = Vec::with_capacity(dict.len() + dst.len());
+        let spare = &mut window.spare_capacity_mut()[..dict.len() + dst.len()];
+        spare[..dict.len()].write_copy_of_slice(dict);
+
+        let (_, written, status) = decode_window(src, spare, dict.len());
+        // SAFETY: the dictionary and the `written` bytes decoded after it are initialized
+        unsafe { window.set_len(dict.len() + written) };
+        dst[..written].copy_from_slice(&window[dict.len()..]);
+        status.map(|()| written)
+    }
+
+    /// Like `decompress_into`, but `dst` may be uninitialized: it is only
+    /// written to, and on success its first `len` bytes are initialized.
+    pub fn decompress_into_uninit(
+        &self,
+        src: &[u8],
+        dst: &mut [MaybeUninit<u8>],
+    ) -> Result<usize, LzavError> {
+        let (_, written, status) = decode_stream(src, dst);
+        status.map(|()| written)
+    }
+
+    /// Decompressed length the block headers of `data` describe, without
+    /// decoding it; see [`crate::decompressed_size`].
+    pub fn decompress_size(&self, data: &[u8]) -> Result<usize, LzavError> {
+        crate::scan::stream_size(data)
+    }
+
+    /// Checksum of the decompressed contents of `data`.
+    pub fn calculate_initial_checksum(&self, data: &[u8]) -> Result<u32, LzavError> {
+        // Decompress data to calculate checksum
+        let len = self.decompress_size(data)?;
+        let mut result = Vec::with_capacity(len);
+        let (_, written, _) = decode_stream(data, &mut result.spare_capacity_mut()[..len]);
+        // SAFETY: the decoder initialized the first `written` bytes
+        unsafe { result.set_len(written) };
+
+        Ok(self.calculate_checksum(&result))
+    }
+
+    #[inline(always)]
+    fn calculate_checksum(&self, data: &[u8]) -> u32 {
+        crc32(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::LZAV_MAX_EXPANSION;
+
+    #[test]
+    fn test_empty_input() {
+        let data = b"";
+        let mut compressor = SWARCompressor::new();
+        let compressed = compressor.compress(data);
+        let decompressed = compressor.decompress(&compressed).unwrap();
+        assert_eq!(decompressed, data);
+    }
+
+    #[test]
+    fn test_compression_decompression() {
+        let data = b"This is a test string with some repetition. \
+                    This is a test string with some repetition. \
+                    This is a test string with some repetition. \
+                    This is a test string with some repetition.";
+        
+        let mut compressor = SWARCompressor::new();
+        let compressed = compressor.compress(data);
+        let compressed_len = compressed.data.len(); // Store length locally
+        
+        println!("Original size: {}, Compressed size: {}", data.len(), compressed_len);
+        assert!(compressed_len < data.len());
+        
+        let decompressed = compressor.decompress(&compressed).unwrap();
+        assert_eq!(decompressed, data);
+    }
+
+    #[test]
+    fn test_large_repetitive_input() {
+        let data: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let mut compressor = SWARCompressor::new();
+        let compressed = compressor.compress(&data);
+        
+        println!("Original size: {}, Compressed size: {}", data.len(), compressed.data.len());
+        assert!(compressed.data.len() < data.len());
+        
+        let decompressed = compressor.decompress(&compressed).unwrap();
+        assert_eq!(decompressed, data);
+    }
+
+    #[test]
+    fn test_highly_repetitive() {
+        let mut data = Vec::with_capacity(1000);
+        for _ in 0..100 {
+            data.extend_from_slice(b"HelloWorld");
+        }
+        
+        let mut compressor = SWARCompressor::new();
+        let compressed = compressor.compress(&data);
+        
+        println!("Highly repetitive - Original size: {}, Compressed size: {}", 
+                data.len(), compressed.data.len());
+        assert!(compressed.data.len() < data.len());
+        
+        let decompressed = compressor.decompress(&compressed).unwrap();
+        assert_eq!(decompressed, data);
+    }
+
+    #[test]
+    fn test_decompress_partial_prefix() {
+        let mut data = Vec::new();
+        for i in 0..200u32 {
+            data.extend_from_slice(&(i % 7).to_le_bytes()); // plenty of zero bytes
+        }
+        let mut compressor = SWARCompressor::new();
+        let compressed = compressor.compress(&data);
+
+        let mut dst = vec![0u8; 101];
+        let (consumed, written) = compressor.decompress_partial(&compressed.data, &mut dst);
+        assert_eq!(written, dst.len());
+        assert!(consumed < compressed.data.len());
+        assert_eq!(&dst[..], &data[..written]);
+
+        let mut full = vec![0u8; data.len()];
+        let (consumed, written) = compressor.decompress_partial(&compressed.data, &mut full);
+        assert_eq!((consumed, written), (compressed.data.len(), data.len()));
+        assert_eq!(full, data);
+    }
+
+    #[test]
+    fn test_decompress_partial_truncated_src() {
+        let data = b"partial partial partial partial decode decode decode".repeat(4);
+        let mut compressor = SWARCompressor::new();
+        let compressed = compressor.compress(&data);
+
+        for len in 0..compressed.data.len() {
+            let mut dst = vec![0u8; data.len()];
+            let (consumed, written) = compressor.decompress_partial(&compressed.data[..len], &mut dst);
+            assert!(consumed <= len);
+            assert_eq!(&dst[..written], &data[..written]);
+        }
+    }
+
+    // Decode `stream` with `decode_resume`, letting `dst` grow by `step`
+    // bytes whenever a call writes nothing
+    fn resume_all(stream: &[u8], len: usize, step: usize) -> Result<Vec<u8>, LzavError> {
+        let mut dst = vec![0u8; len];
+        let mut state = ResumeState::default();
+        let (mut pos, mut limit) = (0, step.min(len));
+        while pos < stream.len() {
+            let (consumed, written) = decode_resume(&stream[pos..], &mut dst[..limit], &mut state)?;
+            pos += consumed;
+            if written == 0 && consumed == 0 {
+                assert!(limit < len, "stuck at {pos} of {} with a full dst", stream.len());
+                limit = (limit + step).min(len);
+            }
+        }
+        assert_eq!(state.written(), len);
+        Ok(dst)
+    }
+
+    #[test]
+    fn test_decode_resume() {
+        let mut data = b"resumable decode, stopping between blocks; ".repeat(300);
+        let mut state = 0x2545_F491u32;
+        data.extend((0..3000).map(|_| {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 24) as u8
+        }));
+        data.extend_from_slice(&b"x".repeat(5000));
+        let stream = SWARCompressor::new().compress(&data).data;
+
+        for step in [1, 7, 100, 4096, data.len()] {
+            assert!(resume_all(&stream, data.len(), step).unwrap() == data, "step {step}");
         }
-        
+
+        // Tiny and stored streams resume too
+        let tiny = SWARCompressor::new().compress(b"tiny").data;
+        assert_eq!(resume_all(&tiny, 4, 1).unwrap(), b"tiny");
+        let stored = [&[crate::LZAV_STORED][..], b"stored bytes"].concat();
+        assert_eq!(resume_all(&stored, 12, 5).unwrap(), b"stored bytes");
+
+        let mut corrupt = stream.clone();
+        corrupt[0] = 0x70;
+        assert_eq!(resume_all(&corrupt, data.len(), 100), Err(LzavError::UnknownFormat));
+        let mut state = ResumeState::default();
+        let mut dst = vec![0u8; 64];
+        decode_resume(&stream, &mut dst, &mut state).unwrap();
+        assert_eq!(decode_resume(&stream[1..], &mut dst[..10], &mut state), Err(LzavError::InvalidParams));
+    }
+
+    #[test]
+    fn test_decompress_corrupt_input() {
+        let data = b"corrupt streams must error, corrupt streams must never panic".repeat(8);
         let mut compressor = SWARCompressor::new();
         let compressed = compressor.compress(&data);
-        
-        println!("Highly repetitive - Original size: {}, Compressed size: {}", 
-                data.len(), compressed.data.len());
-        assert!(compressed.data.len() < data.len());
-        
-        let decompressed = compressor.decompress(&compressed);
-        assert_eq!(decompressed, data);
+
+        let mut bad_checksum = compressed.clone();
+        bad_checksum.metadata.checksum ^= 1;
+        let actual = compressed.metadata.checksum;
+        assert_eq!(compressor.decompress(&bad_checksum), Err(LzavError::ChecksumMismatch { expected: actual ^ 1, actual }));
+
+        // A stream that ends early reports how far it got
+        let mut too_long = compressed.clone();
+        too_long.metadata.original_size += 10;
+        assert_eq!(compressor.decompress(&too_long), Err(LzavError::Corrupt { at: data.len() }));
+
+        let mut bad_format = compressed.clone();
+        bad_format.data[0] = 0x76;
+        assert_eq!(compressor.decompress(&bad_format), Err(LzavError::UnknownFormat));
+
+        for i in 1..compressed.data.len() {
+            for flip in [0x01, 0x10, 0x80, 0xFF] {
+                let mut corrupt = compressed.clone();
+                corrupt.data[i] ^= flip;
+                let _ = compressor.decompress(&corrupt);
+            }
+            let mut truncated = compressed.clone();
+            truncated.data.truncate(i);
+            assert!(compressor.decompress(&truncated).is_err());
+        }
+    }
+
+    #[test]
+    fn test_compress_with_reuse() {
+        let mut reused = SWARCompressor::new();
+        let mut dst = vec![0u8; 8192];
+
+        for i in 0..16u8 {
+            let data: Vec<u8> = b"reusable compressor context "
+                .iter()
+                .map(|b| b ^ (i & 3))
+                .cycle()
+                .take(4096)
+                .collect();
+            let len = reused.compress_with(&data, &mut dst);
+            assert!(len > 0);
+            assert_eq!(&dst[..len as usize], &SWARCompressor::new().compress(&data).data[..]);
+            if i % 4 == 0 {
+                reused.reset();
+            }
+        }
+
+        assert_eq!(reused.compress_with(b"", &mut dst), 0);
+    }
+
+    #[test]
+    fn test_decompress_to_reuses_buffer() {
+        let mut compressor = SWARCompressor::new();
+        let frames: Vec<(Vec<u8>, CompressedData)> = (1..=8usize)
+            .map(|i| {
+                let data = b"reused output buffer ".repeat(40 / i);
+                let compressed = compressor.compress(&data);
+                (data, compressed)
+            })
+            .collect();
+
+        // The first frame is the largest, so later ones never reallocate
+        let mut out = Vec::new();
+        compressor.decompress_to(&frames[0].1, &mut out).unwrap();
+        let (ptr, capacity) = (out.as_ptr(), out.capacity());
+        for (data, compressed) in &frames {
+            assert_eq!(compressor.decompress_to(compressed, &mut out), Ok(data.len()));
+            assert_eq!(&out, data);
+            assert_eq!((out.as_ptr(), out.capacity()), (ptr, capacity));
+        }
+
+        let mut corrupt = frames[2].1.clone();
+        corrupt.metadata.original_size += 1;
+        assert!(compressor.decompress_to(&corrupt, &mut out).is_err());
+        assert_eq!(compressor.decompress_to(&frames[3].1, &mut out), Ok(frames[3].0.len()));
+        assert_eq!(out, frames[3].0);
+    }
+
+    #[test]
+    fn test_decompress_size_rejects_hostile_lengths() {
+        let compressor = SWARCompressor::new();
+
+        // A 20 byte stream whose literal block claims about 4 GiB
+        let mut hostile = b"\x26\x00\xFF\xFF\xFF\xFF\x0F".to_vec();
+        hostile.resize(20, 0);
+        assert_eq!(compressor.decompress_size(&hostile), Err(LzavError::SourceOutOfBounds));
+        assert!(compressor.calculate_initial_checksum(&hostile).is_err());
+
+        // Back to back maximal references stay within the expansion bound
+        let mut dense = vec![0x2F, 0x01, b'a'];
+        for _ in 0..16 {
+            dense.extend_from_slice(b"\x10\x00\xFF\xFF");
+        }
+        dense.extend_from_slice(&[0; LZAV_LIT_FIN]);
+        let size = compressor.decompress_size(&dense).unwrap();
+        assert!(size > 16 * LZAV_REF_LEN && size <= dense.len() * LZAV_MAX_EXPANSION);
+
+        assert_eq!(compressor.decompress_size(b"\x26"), Err(LzavError::SourceOutOfBounds));
+        assert_eq!(compressor.decompress_size(b"\x76\x00\x00"), Err(LzavError::UnknownFormat));
+        let data = b"plausible sizes pass, plausible sizes pass".repeat(30);
+        let compressed = SWARCompressor::new().compress(&data);
+        assert_eq!(compressor.decompress_size(&compressed.data), Ok(data.len()));
+    }
+
+    #[test]
+    fn test_dictionary_small_records() {
+        let record = |i: usize| {
+            let mut r = format!(
+                "{{\"id\":{i},\"user\":\"user{}\",\"event\":\"page_view\",\"path\":\"/products/{}\",\
+                 \"agent\":\"Mozilla/5.0 (X11; Linux x86_64)\",\"status\":200,\"ms\":{}}}",
+                i * 7 % 13,
+                i * 31 % 97,
+                i * 17 % 500
+            )
+            .into_bytes();
+            r.resize(200, b' ');
+            r
+        };
+        let dict = [record(1000), record(2001)].concat();
+
+        let mut compressor = SWARCompressor::new();
+        let (mut plain, mut primed) = (0, 0);
+        let mut dst = vec![0u8; 512];
+        for i in 0..100 {
+            let data = record(i);
+            plain += compressor.compress_with(&data, &mut dst);
+
+            let len = compressor.compress_with_dict(&data, &mut dst, &dict);
+            assert!(len > 0);
+            primed += len;
+            let stream = &dst[..len as usize];
+            assert!(!is_tiny_stream(stream));
+
+            let mut out = vec![0u8; data.len()];
+            assert_eq!(compressor.decompress_with_dict(stream, &mut out, &dict), Ok(data.len()));
+            assert_eq!(out, data);
+            // References into the dictionary cannot resolve without it
+            assert_ne!(compressor.decompress_into(stream, &mut out).ok(), Some(data.len()));
+        }
+        assert!(primed * 2 < plain);
+
+        // Tiny inputs and an empty dictionary fall back to plain streams
+        for data in [&b"tiny"[..], &record(5)[..]] {
+            let len = compressor.compress_with_dict(data, &mut dst, b"") as usize;
+            let mut out = vec![0u8; data.len()];
+            assert_eq!(compressor.decompress_into(&dst[..len], &mut out), Ok(data.len()));
+            assert_eq!(out, data);
+        }
+    }
+
+    #[test]
+    fn test_compress_small_dst_is_dstoob() {
+        use crate::errors::LZAV_E_DSTOOB;
+
+        let data = b"destination too small, destination too small".repeat(4);
+        let mut dst = [0u8; 16];
+
+        // Both the bound precheck and an oversized stream report LZAV_E_DSTOOB,
+        // never the decoder's LZAV_E_DSTLEN
+        assert_eq!(crate::rust::compress_default(&data, &mut dst), LZAV_E_DSTOOB);
+        assert_eq!(SWARCompressor::new().compress_with(&data, &mut dst), LZAV_E_DSTOOB);
+        let mut scratch = vec![0u8; 1 << 16];
+        assert_eq!(
+            SWARCompressor::new().compress_with_scratch(&data, &mut dst, &mut scratch),
+            LZAV_E_DSTOOB
+        );
+    }
+
+    #[test]
+    fn test_decompress_fmt1_stream() {
+        // Format 1, REF_MIN 5: ten literals carrying offset bits, an
+        // overlapping reference with a length byte, then six final literals
+        let stream = b"\x15\x8Aabcdefghij\x90\x00\x0A\x06klmnop";
+        let expected = [b"abcdefghij".repeat(4), b"klmnop".to_vec()].concat();
+
+        let compressor = SWARCompressor::new();
+        assert_eq!(compressor.decompress_size(stream), Ok(expected.len()));
+
+        let mut dst = vec![0u8; expected.len()];
+        let (consumed, written) = compressor.decompress_partial(stream, &mut dst);
+        assert_eq!((consumed, written), (stream.len(), expected.len()));
+        assert_eq!(dst, expected);
+    }
+
+    #[test]
+    fn test_checksum_detects_bit_flip() {
+        let data = b"checksummed payload, checksummed payload, checksummed payload".to_vec();
+        let mut compressor = SWARCompressor::new();
+        let compressed = compressor.compress(&data);
+
+        // The final bytes of a stream are always literals
+        let last = compressed.data.len() - 1;
+        for bit in 0..8 {
+            let mut corrupt = compressed.clone();
+            corrupt.data[last] ^= 1 << bit;
+            let result = compressor.decompress(&corrupt);
+            assert!(matches!(result, Err(LzavError::ChecksumMismatch { expected, .. }) if expected == compressed.metadata.checksum));
+        }
+    }
+
+    #[test]
+    fn test_unverified_decompress() {
+        let data = b"integrity checked elsewhere, integrity checked elsewhere".repeat(4);
+        let mut compressor = SWARCompressor::new();
+        let compressed = compressor.compress(&data);
+        compressor.set_verify_checksum(false);
+
+        let mut bad_checksum = compressed.clone();
+        bad_checksum.metadata.checksum ^= 1;
+        assert_eq!(compressor.decompress(&bad_checksum), Ok(data));
+
+        // Corrupt references are still rejected, not followed out of bounds
+        for i in 1..compressed.data.len() {
+            let mut corrupt = compressed.clone();
+            corrupt.data[i] ^= 0xFF;
+            let _ = compressor.decompress(&corrupt);
+        }
+
+        let mut truncated = compressed.clone();
+        truncated.data.truncate(compressed.data.len() / 2);
+        assert!(compressor.decompress(&truncated).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_compressed_data_serde_roundtrip() {
+        let data = b"serialized alongside its metadata, serialized alongside".repeat(8);
+        let mut compressor = SWARCompressor::new();
+        let compressed = compressor.compress(&data);
+
+        let json = serde_json::to_string(&compressed).unwrap();
+        let restored: CompressedData = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.metadata.original_size, compressed.metadata.original_size);
+        assert_eq!(restored.metadata.checksum, compressed.metadata.checksum);
+        assert_eq!(restored.data, compressed.data);
+        assert_eq!(compressor.decompress(&restored).unwrap(), data);
+    }
+
+    #[test]
+    fn test_compressed_data_from_bytes_and_frames() {
+        use crate::checksum::ChecksumKind;
+        use crate::frame::{compress_frame, compress_frame_with, FRAME_END};
+
+        let data = b"parsed from a frame header, parsed from a frame header".repeat(20);
+        let compressor = SWARCompressor::new();
+        let compressed = SWARCompressor::new().compress(&data);
+        let rebuilt = CompressedData::from_bytes(
+            compressed.data.clone(),
+            compressed.metadata.original_size,
+            compressed.metadata.checksum,
+        );
+        assert_eq!(rebuilt, compressed);
+
+        let mut noise = vec![0u8; 500];
+        let mut state = 0x9E3779B9u32;
+        for byte in &mut noise {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            *byte = (state >> 24) as u8;
+        }
+        // Compressed, stored and empty frames all decode to their input
+        for input in [&data[..], &noise, b""] {
+            let parsed = CompressedData::decode(&compress_frame(input)).unwrap();
+            assert_eq!(parsed.original_len(), input.len());
+            assert_eq!(parsed.metadata.checksum, crc32(input));
+            assert_eq!(compressor.decompress(&parsed).unwrap(), input);
+        }
+
+        let unchecked = compress_frame_with(&data, ChecksumKind::None);
+        assert_eq!(CompressedData::decode(&unchecked), Err(LzavError::UnknownFormat));
+        assert_eq!(CompressedData::decode(&FRAME_END), Err(LzavError::InvalidParams));
+        assert_eq!(CompressedData::decode(b"LZAV"), Err(LzavError::SourceOutOfBounds));
+    }
+
+    #[test]
+    fn test_compressed_data_keys_a_cache() {
+        use std::collections::HashSet;
+
+        let data = b"deduplicated by compressed content, deduplicated".repeat(6);
+        let mut compressor = SWARCompressor::new();
+        let first = compressor.compress(&data);
+        let second = compressor.compress(&data);
+        let other = compressor.compress(b"something else entirely, something else");
+
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert_eq!(first.compressed_len(), first.data.len());
+        assert_eq!(first.original_len(), data.len());
+        assert_eq!(first.as_bytes(), &first.data[..]);
+
+        let cache: HashSet<_> = [first, second, other].into_iter().collect();
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_decompress_into() {
+        let data = b"single pass decode, single pass decode, single pass decode".to_vec();
+        let mut compressor = SWARCompressor::new();
+        let compressed = compressor.compress(&data);
+
+        let mut dst = vec![0u8; data.len() + 16];
+        assert_eq!(compressor.decompress_into(&compressed.data, &mut dst), Ok(data.len()));
+        assert_eq!(&dst[..data.len()], &data[..]);
+
+        let mut short = vec![0u8; data.len() - 1];
+        assert_eq!(
+            compressor.decompress_into(&compressed.data, &mut short),
+            Err(LzavError::DestOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn test_match_len_dispatch_matches_scalar() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(13);
+        // Only x86-64 and aarch64 add SIMD routines below
+        #[allow(unused_mut)]
+        let mut routines: Vec<MatchLenFn> = vec![match_len];
+        #[cfg(target_arch = "x86_64")]
+        {
+            if std::is_x86_feature_detected!("sse2") {
+                routines.push(match_len_sse2);
+            }
+            if std::is_x86_feature_detected!("avx2") {
+                routines.push(match_len_avx2);
+            }
+        }
+        #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                routines.push(match_len_neon);
+            }
+        }
+
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..300);
+            let a: Vec<u8> = (0..len).map(|_| rng.gen_range(0..2)).collect();
+            let mut b = a.clone();
+            if len > 0 && rng.gen_bool(0.8) {
+                let at = rng.gen_range(0..len);
+                b[at] ^= 1;
+            }
+            let limit = rng.gen_range(0..=len);
+
+            let expected = match_len_swar(&a, &b, limit);
+            for routine in &routines {
+                assert_eq!(routine(&a, &b, limit), expected);
+            }
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_match_len_sse2_first_mismatch() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(101);
+        let data: Vec<u8> = (0..1024).map(|_| rng.gen()).collect();
+
+        for _ in 0..2000 {
+            // Random offsets so the 16 byte loads straddle every alignment
+            let len = rng.gen_range(0..200);
+            let start = rng.gen_range(0..data.len() - len);
+            let a = &data[start..start + len];
+            let mut b = a.to_vec();
+            for _ in 0..rng.gen_range(0..3) {
+                if len > 0 {
+                    b[rng.gen_range(0..len)] ^= 1 << rng.gen_range(0..8);
+                }
+            }
+
+            let expected = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+            assert_eq!(match_len_sse2(a, &b, len), expected, "length {len}");
+        }
+    }
+
+    #[test]
+    fn test_match_len_unaligned() {
+        let a: Vec<u8> = (0..128u8).collect();
+        for offset in 0..16 {
+            for mismatch in offset..a.len() {
+                let mut b = a.clone();
+                b[mismatch] ^= 0x80;
+                let limit = a.len() - offset;
+                assert_eq!(match_len(&a[offset..], &b[offset..], limit), mismatch - offset);
+            }
+            assert_eq!(match_len(&a[offset..], &a[offset..], a.len() - offset), a.len() - offset);
+        }
+    }
+
+    #[test]
+    fn test_compress_output_is_fixed() {
+        // Loads, hashing and block headers are all explicitly little-endian and
+        // positions are u32, so these bytes must match on every target,
+        // including big-endian and 32-bit ones.
+        let data = b"golden vector: abcabcabcabcabc, golden vector: xyzxyzxyzxyz, golden vector!";
+        let expected: &[u8] = &[
+            0x26, 0x80, 0x05, 0x67, 0x6f, 0x6c, 0x64, 0x65, 0x6e, 0x20, 0x76, 0x65,
+            0x63, 0x74, 0x6f, 0x72, 0x3a, 0x20, 0x61, 0x62, 0x63, 0x61, 0x62, 0x63,
+            0x51, 0x00, 0x05, 0x61, 0x62, 0x63, 0x2c, 0x20, 0x1a, 0x02, 0x86, 0x78,
+            0x79, 0x7a, 0x78, 0x79, 0x7a, 0x51, 0x00, 0x55, 0x07, 0x06, 0x65, 0x63,
+            0x74, 0x6f, 0x72, 0x21,
+        ];
+        assert_eq!(SWARCompressor::new().compress(data).data, expected);
+
+        // Larger input exercising long literals and two-byte offsets
+        let mut state = 0x9E37_79B9u32;
+        let large: Vec<u8> = (0..256 * 1024)
+            .map(|i: u32| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                if (i / 512).is_multiple_of(3) {
+                    (state >> 28) as u8
+                } else {
+                    b"deterministic"[(i % 13) as usize]
+                }
+            })
+            .collect();
+        let compressed = SWARCompressor::new().compress(&large).data;
+        assert_eq!((compressed.len(), crc32(&compressed)), (99_032, 0x0496_CCD5));
+    }
+
+    #[test]
+    fn test_max_chain_limits_search() {
+        // Every "abcdef" shares a hash, but only an older copy continues with "12"
+        let mut data = b"abcdef12345678".to_vec();
+        for i in 0..40u8 {
+            data.extend_from_slice(b"abcdef");
+            data.extend_from_slice(&[b'a' + i % 26, b'A' + i % 26]);
+        }
+        data.extend_from_slice(b"abcdef12345678 tail");
+
+        let mut shallow = SWARCompressor::new();
+        shallow.set_max_chain(1);
+        let deep_out = SWARCompressor::new().compress(&data);
+        let shallow_out = shallow.compress(&data);
+        assert!(deep_out.data.len() < shallow_out.data.len());
+        assert_eq!(shallow.decompress(&shallow_out).unwrap(), data);
+    }
+
+    #[test]
+    fn test_min_match_length_small_records() {
+        // 64-byte records whose repeats are mostly 4 or 5 bytes long
+        let records: Vec<Vec<u8>> = (0..200u32)
+            .map(|i| {
+                let mut record = format!(
+                    "id={:04} ref={:04} src={:04} dst={:04} tag=t{:03} tag=t{:03};",
+                    i,
+                    i ^ 5,
+                    i * 7 % 1000,
+                    i * 13 % 1000,
+                    i % 17,
+                    i % 23
+                )
+                .into_bytes();
+                record.resize(64, b'.');
+                record
+            })
+            .collect();
+
+        let mut total = [0usize; 3];
+        for (min_match, total) in (4..=6).zip(&mut total) {
+            let mut compressor = SWARCompressor::new();
+            compressor.set_min_match_length(min_match).unwrap();
+            for record in &records {
+                let compressed = compressor.compress(record);
+                assert_eq!(compressed.data[0] & 15, min_match as u8);
+                assert_eq!(compressor.decompress(&compressed).unwrap(), *record);
+
+                // Streams with a short minimum match read back through either backend
+                let mut dst = [0u8; 64];
+                assert_eq!(crate::try_decompress(&compressed.data, &mut dst), Ok(64));
+                assert_eq!(&dst, &record[..]);
+                *total += compressed.data.len();
+            }
+        }
+        assert!(total[0] < total[2], "minimum match 4, 5, 6: {total:?} bytes");
+
+        let mut compressor = SWARCompressor::new();
+        assert_eq!(compressor.set_min_match_length(3), Err(LzavError::InvalidParams));
+        assert_eq!(compressor.set_min_match_length(7), Err(LzavError::InvalidParams));
+        assert_eq!(compressor.min_match, MIN_MATCH_LENGTH);
+    }
+
+    #[test]
+    fn test_min_match_length_longest_reference() {
+        // Runs and periodic data reach the longest reference each length allows
+        let mut data = vec![b'x'; 4096];
+        data.extend((0..8192u32).map(|i| b"abcd"[i as usize % 4]));
+        data.extend_from_slice(b"final literal tail");
+        for min_match in 4..=6 {
+            let mut compressor = SWARCompressor::new();
+            compressor.set_min_match_length(min_match).unwrap();
+            let compressed = compressor.compress(&data);
+            assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_compress_stats_account_for_every_byte() {
+        let text = b"stats for the parse, stats for the parse, stats for it".repeat(200);
+        let mut noise = vec![0u8; 20_000];
+        let mut x = 7u32;
+        for b in &mut noise {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            *b = (x >> 24) as u8;
+        }
+
+        for data in [&b"short"[..], &text, &noise] {
+            let mut compressed = vec![0u8; crate::compress_bound(data.len() as i32) as usize];
+            let (len, stats) = crate::rust::compress_stats(data, &mut compressed).unwrap();
+
+            let mut plain = vec![0u8; compressed.len()];
+            let plain_len = crate::rust::compress(data, &mut plain).unwrap();
+            assert_eq!(compressed[..len], plain[..plain_len]);
+            assert_eq!(stats.literal_bytes + stats.match_bytes, data.len());
+            assert!(stats.literal_runs >= 1 && stats.literal_runs <= stats.matches + 1);
+        }
+
+        let mut compressed = vec![0u8; crate::compress_bound(noise.len() as i32) as usize];
+        let (_, text_stats) = crate::rust::compress_stats(&text, &mut compressed).unwrap();
+        let (_, noise_stats) = crate::rust::compress_stats(&noise, &mut compressed).unwrap();
+        assert!(text_stats.average_match_length() > 100.0);
+        assert!(text_stats.mavg > noise_stats.mavg);
+        assert!(noise_stats.literal_bytes > noise.len() * 9 / 10);
+
+        assert_eq!(crate::rust::compress_stats(b"", &mut compressed), Ok((0, CompressStats::default())));
+    }
+
+    #[test]
+    fn test_match_budget_bounds_search() {
+        // Every record opens with "abcdef", so each search walks a chain of
+        // candidates that match 6 bytes and no more
+        let mut data = Vec::new();
+        for i in 0..4000u16 {
+            data.extend_from_slice(b"abcdef");
+            data.extend_from_slice(&i.to_le_bytes());
+        }
+        let mut compressor = SWARCompressor::with_level(CompressLevel::Max);
+
+        let mut unbounded = usize::MAX;
+        let full = compressor.compress_stream(&data, Vec::new(), &mut unbounded);
+        let mut budget = 1000;
+        let limited = compressor.compress_stream(&data, Vec::new(), &mut budget);
+        assert!(usize::MAX - unbounded > 100 * 1000, "{} attempts", usize::MAX - unbounded);
+        assert_eq!(budget, 0);
+        assert!(limited.len() > full.len());
+
+        let mut dst = vec![0u8; data.len()];
+        for max_match_attempts in [0, 1, 1000] {
+            let mut compressed = vec![0u8; crate::compress_bound(data.len() as i32) as usize];
+            let len = compressor.compress_with_budget(&data, &mut compressed, max_match_attempts);
+            assert!(len > 0);
+            assert_eq!(compressor.decompress_into(&compressed[..len as usize], &mut dst), Ok(data.len()));
+            assert_eq!(dst, data);
+        }
+    }
+
+    #[test]
+    fn test_window_size_bounds_references() {
+        // A 64 KiB noise block repeated once, so its only matches are 64 KiB back
+        let mut state = 0x2545_F491u32;
+        let block: Vec<u8> = (0..64 * 1024)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 24) as u8
+            })
+            .collect();
+        let data = block.repeat(2);
+
+        let mut full = SWARCompressor::new();
+        let mut small = SWARCompressor::new();
+        small.set_window_size(4096).unwrap();
+        assert!(small.scratch_size(data.len()) < full.scratch_size(data.len()));
+
+        let full_out = full.compress(&data);
+        let small_out = small.compress(&data);
+        assert!(full_out.data.len() < data.len() * 3 / 4);
+        assert!(small_out.data.len() > data.len());
+        assert_eq!(full.decompress(&small_out).unwrap(), data);
+
+        // Within the window references are still found
+        let near = block[..2048].repeat(8);
+        let near_out = small.compress(&near);
+        assert!(near_out.data.len() < near.len() / 2);
+        assert_eq!(small.decompress(&near_out).unwrap(), near);
+
+        for bad in [0, 512, 3000, 1 << 24] {
+            assert_eq!(small.set_window_size(bad), Err(LzavError::InvalidParams));
+        }
+        assert_eq!(small.window, 4096);
+
+        // Periods just under the longest match tempt `find_match` into
+        // multiples past the smallest window
+        let mut tiny = SWARCompressor::new();
+        tiny.set_window_size(MIN_WINDOW_SIZE).unwrap();
+        for period in [300, 513, 529] {
+            let periodic = block[..period].repeat(64);
+            let compressed = tiny.compress(&periodic);
+            assert_eq!(tiny.decompress(&compressed).unwrap(), periodic);
+            let distances = reference_distances(&compressed.data);
+            assert!(!distances.is_empty());
+            assert!(distances.iter().all(|&d| d < MIN_WINDOW_SIZE), "period {period}: {distances:?}");
+        }
+    }
+
+    /// Distances of the references in format 2 stream `src`, in order.
+    fn reference_distances(src: &[u8]) -> Vec<usize> {
+        assert_eq!(src[0] >> 4, LZAV_FMT_CUR);
+        let mut distances = Vec::new();
+        let mut carry = Fmt2Carry::default();
+        let mut ip = 1;
+        while ip < src.len() - LZAV_LIT_FIN {
+            let bh = src[ip] as usize;
+            ip += 1;
+            if bh & 0x30 == 0 {
+                let mut cc = bh & 15;
+                if cc == 0 {
+                    let (extra, read) = read_literal_len(&src[ip..]).unwrap();
+                    cc = extra + 16;
+                    ip += read;
+                }
+                carry.cv |= (bh >> 6) << carry.csh;
+                carry.csh += 2;
+                ip += cc;
+            } else {
+                let bt = (bh >> 4) & 3;
+                let o = src[ip..ip + bt].iter().rev().fold(0usize, |acc, &b| acc << 8 | b as usize);
+                ip += bt;
+                distances.push(((bh >> 6) | (o & 0x1FFFFF) << 2) << carry.csh | carry.cv);
+                if bh & 15 == 0 {
+                    ip += if src[ip] == 255 { 2 } else { 1 };
+                }
+                carry.csh = if bt == 3 { 3 } else { 0 };
+                carry.cv = o >> 21;
+            }
+        }
+        distances
+    }
+
+    #[test]
+    fn test_back_to_back_references_roundtrip() {
+        // Matches that start right at the anchor left by the previous match,
+        // with no literals in between; `write_block` asserts `d >= 1`
+        let inputs: [&[u8]; 3] = [
+            &b"a".repeat(600),
+            &b"0123456789".repeat(40),
+            &[b"abcdefgh".repeat(3), b"12345678".repeat(3), b"abcdefgh12345678".repeat(4)].concat(),
+        ];
+
+        for data in inputs {
+            for level in [CompressLevel::Fast, CompressLevel::Default, CompressLevel::Max] {
+                let mut compressor = SWARCompressor::with_level(level);
+                let compressed = compressor.compress(data);
+                assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lazy_match_prefers_longer_next_match() {
+        // At the last "abcdef" the greedy parse takes a 6-byte match, while a
+        // 21-byte match starts one byte later
+        let data = b"abcdef--bcdefghijklmnopqrstu++abcdefghijklmnopqrstu==tail!!";
+
+        let mut lazy = SWARCompressor::with_level(CompressLevel::Max);
+        let mut greedy = SWARCompressor::with_level(CompressLevel::Max);
+        greedy.search.lazy = false;
+
+        let lazy_out = lazy.compress(data);
+        let greedy_out = greedy.compress(data);
+        assert!(lazy_out.data.len() < greedy_out.data.len());
+        assert_eq!(lazy.decompress(&lazy_out).unwrap(), data);
+    }
+
+    #[test]
+    fn test_tail_positions_stay_hashable() {
+        // Every length past the tiny limit, so each tail layout is indexed
+        // under the debug assertions in `hash` and `insert`
+        let pattern = b"tail hashing tail hashing tail";
+        for len in LZAV_TINY_MAX + 1..200 {
+            let data: Vec<u8> = pattern.iter().cycle().take(len).copied().collect();
+            for level in [CompressLevel::Fast, CompressLevel::Max] {
+                let mut compressor = SWARCompressor::with_level(level);
+                let compressed = compressor.compress(&data);
+                assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_run_uses_long_matches() {
+        // With 258-byte matches capped at the chain distance this run took
+        // about 15 KB at the default level and was stored raw at Fast
+        let data = vec![0xAB; 60000];
+        for level in [CompressLevel::Fast, CompressLevel::Default, CompressLevel::Max] {
+            let mut compressor = SWARCompressor::with_level(level);
+            let compressed = compressor.compress(&data);
+            assert!(compressed.data.len() < 600, "{:?}: {} bytes", level, compressed.data.len());
+            assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+        }
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_log_feature_reports_parse() {
+        use std::sync::Mutex;
+
+        struct Capture(Mutex<Vec<String>>);
+        impl log::Log for Capture {
+            fn enabled(&self, metadata: &log::Metadata) -> bool {
+                metadata.target() == "rlzav"
+            }
+            fn log(&self, record: &log::Record) {
+                if self.enabled(record.metadata()) {
+                    self.0.lock().unwrap().push(record.args().to_string());
+                }
+            }
+            fn flush(&self) {}
+        }
+        static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+        log::set_logger(&CAPTURE).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let data = b"logged parse, logged parse, logged parse".repeat(40);
+        let mut dst = vec![0u8; crate::rust::compress_bound(data.len() as i32) as usize];
+        assert!(SWARCompressor::new().compress_with(&data, &mut dst) > 0);
+
+        let lines = CAPTURE.0.lock().unwrap();
+        let table = format!("chain links for {} bytes", data.len());
+        let summary = format!("{} bytes to ", data.len());
+        assert!(lines.iter().any(|l| l.contains(&table)), "{lines:?}");
+        assert!(lines.iter().any(|l| l.starts_with(&summary) && l.contains("matches of")), "{lines:?}");
+    }
+
+    #[test]
+    fn test_max_distance_references() {
+        // Noise up front and zeros after it, so the only matches for the
+        // copies below are the far ones
+        let mut state = 0x9E37_79B9u32;
+        let mut data = vec![0u8; WINDOW_SIZE + 200];
+        for byte in &mut data[..200] {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            *byte = (state >> 24) as u8;
+        }
+
+        // Two back-to-back 3-byte-offset references, the second taking offset
+        // bits carried into the first's header
+        let first = WINDOW_SIZE - 1;
+        data.copy_within(0..64, first);
+        let second = first + 64 + 10;
+        data.copy_within(second - (WINDOW_SIZE - 2)..second - (WINDOW_SIZE - 2) + 64, second);
+
+        let mut writer = Fmt2Writer::new(Vec::new(), data.len());
+        writer.write_block(&data[..first], 64, WINDOW_SIZE - 1);
+        writer.write_block(&data[first + 64..second], 64, WINDOW_SIZE - 2);
+        writer.write_final(&data[second + 64..]);
+
+        let mut decompressed = vec![0u8; data.len()];
+        let mut compressor = SWARCompressor::new();
+        assert_eq!(compressor.decompress_into(&writer.out, &mut decompressed), Ok(data.len()));
+        assert!(decompressed == data);
+
+        // The compressor finds the same far matches: the copies cost far less
+        // than the same bytes as fresh noise
+        let compressed = compressor.compress(&data);
+        assert!(compressor.decompress(&compressed).unwrap() == data);
+
+        let mut noisy = data.clone();
+        for i in (first..first + 64).chain(second..second + 64) {
+            noisy[i] = noisy[i].wrapping_add(i as u8 | 1);
+        }
+        let noisy_len = compressor.compress(&noisy).data.len();
+        assert!(compressed.data.len() + 100 < noisy_len);
+    }
+
+    #[test]
+    fn test_run_fast_path() {
+        // Runs just under, at and over RLE_MIN_RUN, long runs, and runs
+        // touching the start and the final literals
+        let mut state = 0x2545_F491u32;
+        let mut noise = |n: usize| -> Vec<u8> {
+            (0..n)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect()
+        };
+        let mut data = vec![7u8; 100];
+        for (byte, len) in [(0u8, 31), (1, 32), (2, 33), (0, 1000), (0xFF, 5000), (3, 40)] {
+            data.extend(noise(50));
+            data.extend(core::iter::repeat_n(byte, len));
+        }
+        data.extend(vec![9u8; 64]);
+
+        let mut compressor = SWARCompressor::new();
+        let compressed = compressor.compress(&data);
+        assert!(compressed.data.len() < 600, "{} bytes", compressed.data.len());
+        assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+
+        let zeros = vec![0u8; crate::LZAV_WIN_LEN];
+        let compressed = compressor.compress(&zeros);
+        assert!(compressed.data.len() < zeros.len() / 128);
+        assert_eq!(compressor.decompress(&compressed).unwrap(), zeros);
+    }
+
+    #[test]
+    fn test_compress_with_scratch() {
+        let data = b"caller provided scratch space, caller provided scratch space".repeat(50);
+        let expected = SWARCompressor::new().compress(&data).data;
+
+        let mut compressor = SWARCompressor::new();
+        let mut scratch = vec![0u8; compressor.scratch_size(data.len()) + 3];
+        let mut dst = vec![0u8; data.len() * 2];
+        for scratch in [&mut scratch[..], &mut [0u8; 64][..]] {
+            let len = compressor.compress_with_scratch(&data, &mut dst, scratch);
+            assert_eq!(&dst[..len as usize], &expected[..]);
+        }
     }
 
     #[test]
@@ -552,4 +2524,35 @@ mod tests {
         let c = Swar::from_bytes(b"AAAAAAAA");
         assert_eq!(a.find_match_length(&c), 8);
     }
+
+    #[test]
+    fn test_swar_match_length_is_byte_order_independent() {
+        // Byte 0 is the low byte whatever the target's endianness
+        assert_eq!(Swar::from_bytes(&[1, 0, 0, 0, 0, 0, 0, 0]).data, 1);
+        assert_eq!(Swar::from_bytes(&[0, 0, 0, 0, 0, 0, 0, 1]).data, 1 << 56);
+        assert_eq!(Swar::from_bytes(&[0, 1]).data, 1 << 8);
+
+        // Against a scalar compare, with a first difference at every byte and
+        // further differences after it
+        let mut state = 0x9E37_79B9u32;
+        let mut next = || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 24) as u8
+        };
+        for _ in 0..500 {
+            let a: [u8; 8] = core::array::from_fn(|_| next());
+            let mut b = a;
+            let first = next() as usize % 9;
+            for (i, byte) in b.iter_mut().enumerate().skip(first) {
+                if i == first || next() & 1 == 1 {
+                    *byte ^= next() | 1;
+                }
+            }
+            for len in [8, 5, 1] {
+                let expected = a[..len].iter().zip(&b[..len]).take_while(|(x, y)| x == y).count();
+                let found = Swar::from_bytes(&a[..len]).find_match_length(&Swar::from_bytes(&b[..len]));
+                assert_eq!(found.min(len), expected, "{a:?} vs {b:?}, {len} bytes");
+            }
+        }
+    }
 }
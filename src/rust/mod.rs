@@ -1,70 +1,241 @@
+#[cfg(feature = "capi")]
+pub mod capi;
 mod lzav;
-use lzav::*;
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub use lzav::{CompressStats, ResumeState, SWARCompressor};
+#[cfg(feature = "std")]
+pub use pool::{CompressorPool, PooledCompressor};
+
+use core::mem::MaybeUninit;
+
+use crate::errors::LzavError;
+use crate::sink::Sink;
+use crate::CompressLevel;
+
+/// Compress `src` into `dst`, returning the number of bytes written. Empty
+/// input compresses to an empty stream, which decompresses to nothing.
+pub fn compress(src: &[u8], dst: &mut [u8]) -> Result<usize, LzavError> {
+    compress_level(src, dst, CompressLevel::Default)
+}
+
+/// Compress `src` into `dst` with the given match search effort, returning
+/// the number of bytes written.
+pub fn compress_level(src: &[u8], dst: &mut [u8], level: CompressLevel) -> Result<usize, LzavError> {
+    if src.is_empty() {
+        return Ok(0);
+    }
+    if dst.is_empty() {
+        return Err(LzavError::InvalidParams);
+    }
+
+    if dst.len() < compress_bound(src.len() as i32) as usize {
+        return Err(LzavError::DestOutOfBounds);
+    }
+
+    LzavError::check(SWARCompressor::with_level(level).compress_with(src, dst))
+}
 
 /// Compress data using the Rust SWAR-based backend and return `i32` for compatibility.
 pub fn compress_default(src: &[u8], dst: &mut [u8]) -> i32 {
+    match compress(src, dst) {
+        Ok(len) => len as i32,
+        Err(e) => e.code(),
+    }
+}
+
+/// Compress `src` and push the stream to `sink` in one piece, returning its
+/// length; a sink that fails receives nothing. The stream is completed in
+/// the compressor's own buffer first, see [`SWARCompressor::compress_into`].
+/// The error bound matches the C backend, where compression itself can fail.
+/// See [`Sink`] for the destinations it takes.
+pub fn compress_into<S: Sink>(src: &[u8], sink: S) -> Result<usize, S::Error>
+where
+    S::Error: From<LzavError>,
+{
+    SWARCompressor::new().compress_into(src, sink)
+}
+
+/// Compress `src` into `dst` examining at most `max_match_attempts` match
+/// candidates in total, then writing the rest as literals, so the time spent
+/// is bounded for any input. See [`SWARCompressor::compress_with_budget`].
+pub fn compress_with_budget(src: &[u8], dst: &mut [u8], max_match_attempts: usize) -> Result<usize, LzavError> {
+    LzavError::check(SWARCompressor::new().compress_with_budget(src, dst, max_match_attempts))
+}
+
+/// Compress `src` into `dst` like [`compress`], also returning how the input
+/// was parsed. The stream is the same as `compress` writes.
+pub fn compress_stats(src: &[u8], dst: &mut [u8]) -> Result<(usize, CompressStats), LzavError> {
     let mut compressor = SWARCompressor::new();
-    
-    if src.is_empty() || dst.is_empty() {
-        return -1; // LZAV_E_PARAMS
+    let len = LzavError::check(compressor.compress_with(src, dst))?;
+    Ok((len, compressor.stats()))
+}
+
+/// Minimum scratch size in bytes for `compress_with_scratch` to use the
+/// caller's buffer for `srcl` input bytes.
+pub fn calculate_hash_table_size(srcl: i32) -> usize {
+    SWARCompressor::new().scratch_size(srcl.max(0) as usize)
+}
+
+/// Compress with the match index kept in `scratch`, falling back to
+/// internal allocation when it is smaller than `calculate_hash_table_size`.
+/// [`AlignedBuffer::for_scratch`](crate::AlignedBuffer::for_scratch)
+/// allocates a suitable buffer.
+pub fn compress_with_scratch(src: &[u8], dst: &mut [u8], scratch: &mut [u8]) -> i32 {
+    if !src.is_empty() && dst.len() < compress_bound(src.len() as i32) as usize {
+        return LzavError::DestOutOfBounds.code();
+    }
+
+    SWARCompressor::new().compress_with_scratch(src, dst, scratch)
+}
+
+/// Get the compression bound for SWAR-based compression and return `i32`.
+/// Matches `lzav_compress_bound` as both backends write the same format,
+/// saturating at `i32::MAX`; see [`crate::compress_bound_usize`].
+pub const fn compress_bound(srcl: i32) -> i32 {
+    let srcl = if srcl < 0 { 0 } else { srcl as usize };
+    match crate::compress_bound_usize(srcl) {
+        Some(bound) if bound <= i32::MAX as usize => bound as i32,
+        _ => i32::MAX,
+    }
+}
+
+/// Decompress `src` into `dst`, which must be exactly the decompressed
+/// length, returning it. Like `lzav_decompress`, a stream that ends before
+/// filling `dst` is `Corrupt` at the bytes it produced; see
+/// [`decompress_upto`] when only an upper bound is known. Never touches the
+/// heap: the decoder keeps no tables and writes only into `dst`, which may be
+/// arena or stack memory.
+pub fn try_decompress(src: &[u8], dst: &mut [u8]) -> Result<usize, LzavError> {
+    if src.is_empty() {
+        return Ok(0);
+    }
+    if dst.is_empty() {
+        return Err(LzavError::InvalidParams);
     }
-    
+
+    // Single pass straight into `dst`; raw streams carry no checksum
+    match SWARCompressor::new().decompress_into(src, dst)? {
+        written if written == dst.len() => Ok(written),
+        written => Err(LzavError::Corrupt { at: written }),
+    }
+}
+
+/// Compress `src` into `dst` with a preset dictionary that references may
+/// point into, returning the number of bytes written. The dictionary is not
+/// part of the output; decompress with `decompress_with_dict` and the same
+/// `dict`. Helps most with many small, similar inputs. Rust backend only.
+pub fn compress_with_dict(src: &[u8], dst: &mut [u8], dict: &[u8]) -> Result<usize, LzavError> {
+    if src.is_empty() {
+        return Ok(0);
+    }
+    if dst.is_empty() {
+        return Err(LzavError::InvalidParams);
+    }
+
     if dst.len() < compress_bound(src.len() as i32) as usize {
-        return -4; // LZAV_E_DSTLEN
+        return Err(LzavError::DestOutOfBounds);
     }
 
-    let compressed = compressor.compress(src);
-    if compressed.data.len() > dst.len() {
-        return -4; // LZAV_E_DSTLEN
+    LzavError::check(SWARCompressor::new().compress_with_dict(src, dst, dict))
+}
+
+/// Decompress a stream written by `compress_with_dict` into `dst`, returning
+/// the number of bytes written. `dict` must match the one used to compress.
+pub fn decompress_with_dict(src: &[u8], dst: &mut [u8], dict: &[u8]) -> Result<usize, LzavError> {
+    if src.is_empty() {
+        return Ok(0);
+    }
+    if dst.is_empty() {
+        return Err(LzavError::InvalidParams);
     }
 
-    dst[..compressed.data.len()].copy_from_slice(&compressed.data);
-    compressed.data.len() as i32
+    SWARCompressor::new().decompress_with_dict(src, dst, dict)
 }
 
-/// Get the compression bound for SWAR-based compression and return `i32`.
-pub fn compress_bound(srcl: i32) -> i32 {
-    if srcl <= 0 {
-        return 16;
+/// Decompress `src` into `dst` when only an upper bound on the decompressed
+/// length is known, returning the number of bytes written. A stream that ends
+/// before filling `dst` is not an error; one that needs more room than `dst`
+/// has is `DestOutOfBounds`.
+pub fn decompress_upto(src: &[u8], dst: &mut [u8]) -> Result<usize, LzavError> {
+    if src.is_empty() {
+        return Ok(0);
+    }
+    if dst.is_empty() {
+        return Err(LzavError::InvalidParams);
     }
-    // Add overhead for compression metadata and worst-case scenario
-    (srcl as usize + (srcl as usize / 8) + 16) as i32
+
+    SWARCompressor::new().decompress_into(src, dst)
+}
+
+/// Decompress `src` into possibly uninitialized `dst`, returning the number
+/// of bytes written. `dst` is never read before it is written, so on success
+/// its first `len` bytes are initialized.
+pub fn decompress_into_uninit(src: &[u8], dst: &mut [MaybeUninit<u8>]) -> Result<usize, LzavError> {
+    if src.is_empty() {
+        return Ok(0);
+    }
+    if dst.is_empty() {
+        return Err(LzavError::InvalidParams);
+    }
+
+    SWARCompressor::new().decompress_into_uninit(src, dst)
 }
 
 /// Decompress data using the SWAR-based backend and return `i32` for compatibility.
 pub fn decompress(src: &[u8], dst: &mut [u8]) -> i32 {
-    let compressor = SWARCompressor::new();
-    
-    if src.is_empty() || dst.is_empty() {
-        return -1; // LZAV_E_PARAMS
+    match try_decompress(src, dst) {
+        Ok(len) => len as i32,
+        Err(e) => e.code(),
     }
+}
+
+/// Decompress as much of `src` as fits into `dst` and return the bytes written.
+pub fn decompress_partial(src: &[u8], dst: &mut [u8]) -> i32 {
+    decompress_partial_counts(src, dst).1 as i32
+}
+
+/// Decompress as much of `src` as fits into `dst`, returning
+/// `(src_consumed, dst_written)`. Never reads past `src.len()`.
+pub fn decompress_partial_counts(src: &[u8], dst: &mut [u8]) -> (usize, usize) {
+    SWARCompressor::new().decompress_partial(src, dst)
+}
 
-    // First decompress to get actual size
-    let size_check = compressor.decompress_size(src);
-    if size_check > dst.len() {
-        return -4; // LZAV_E_DSTLEN
+/// Decompress as much of `src` as fits into `dst`, stopping only between
+/// blocks, and return `(src_consumed, dst_written)` for this call. Call again
+/// with `&src[src_consumed..]` to continue where it stopped; `state` holds
+/// what the format carries from block to block.
+///
+/// References reach back into earlier output, so `dst` is the whole output
+/// buffer and must still hold what previous calls wrote, which ends at
+/// `state.written()`; pass it again, or a larger copy, rather than a fresh
+/// buffer. `src` must run to the end of the stream, since its last blocks
+/// are recognized by where it ends. A call that writes nothing needs more
+/// room in `dst`. Format 1 streams are `UnknownFormat`. Rust backend only.
+pub fn decompress_resume(src: &[u8], dst: &mut [u8], state: &mut ResumeState) -> Result<(usize, usize), LzavError> {
+    lzav::decode_resume(src, dst, state)
+}
+
+/// The pure Rust backend as an [`Lzav`](crate::Lzav) implementation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RustBackend;
+
+impl crate::Lzav for RustBackend {
+    fn compress_default(src: &[u8], dst: &mut [u8]) -> i32 {
+        compress_default(src, dst)
     }
 
-    // Create CompressedData structure from input with correct size
-    let compressed = CompressedData {
-        metadata: FileMetadata {
-            original_size: size_check as u32,
-            checksum: compressor.calculate_initial_checksum(src),
-        },
-        data: src.to_vec(),
-    };
+    fn compress_bound(srcl: i32) -> i32 {
+        compress_bound(srcl)
+    }
 
-    match compressor.decompress(&compressed) {
-        decompressed if decompressed.len() <= dst.len() => {
-            dst[..decompressed.len()].copy_from_slice(&decompressed);
-            decompressed.len() as i32
-        },
-        _ => -4, // LZAV_E_DSTLEN
+    fn decompress(src: &[u8], dst: &mut [u8]) -> i32 {
+        decompress(src, dst)
     }
-}
 
-/// Decompress partial data using the SWAR-based backend and return `i32`.
-pub fn decompress_partial(src: &[u8], dst: &mut [u8]) -> i32 {
-    // For now, partial decompression is same as full decompression
-    decompress(src, dst)
+    fn decompress_partial(src: &[u8], dst: &mut [u8]) -> i32 {
+        decompress_partial(src, dst)
+    }
 }
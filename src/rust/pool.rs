@@ -0,0 +1,115 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, PoisonError};
+
+use super::SWARCompressor;
+use crate::CompressLevel;
+
+/// Shared stock of [`SWARCompressor`]s for compressing on many threads.
+///
+/// [`get`](Self::get) hands out an idle compressor, or a new one when none
+/// is idle, and the guard puts it back on drop, so each task reuses match
+/// tables that earlier tasks already allocated.
+pub struct CompressorPool {
+    idle: Mutex<Vec<SWARCompressor>>,
+    level: CompressLevel,
+}
+
+impl Default for CompressorPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CompressorPool {
+    pub fn new() -> Self {
+        Self::with_level(CompressLevel::Default)
+    }
+
+    /// A pool whose new compressors search with `level` effort.
+    pub fn with_level(level: CompressLevel) -> Self {
+        Self {
+            idle: Mutex::new(Vec::new()),
+            level,
+        }
+    }
+
+    /// Take a compressor from the pool until the guard is dropped. Settings
+    /// changed through the guard stay with that compressor.
+    pub fn get(&self) -> PooledCompressor<'_> {
+        let compressor = self.lock().pop().unwrap_or_else(|| SWARCompressor::with_level(self.level));
+        PooledCompressor {
+            pool: self,
+            compressor: Some(compressor),
+        }
+    }
+
+    /// Number of compressors waiting to be reused.
+    pub fn idle_count(&self) -> usize {
+        self.lock().len()
+    }
+
+    // A panic while holding the lock cannot leave the Vec half-updated
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<SWARCompressor>> {
+        self.idle.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A compressor borrowed from a [`CompressorPool`], returned to it on drop.
+pub struct PooledCompressor<'a> {
+    pool: &'a CompressorPool,
+    compressor: Option<SWARCompressor>,
+}
+
+impl Deref for PooledCompressor<'_> {
+    type Target = SWARCompressor;
+
+    fn deref(&self) -> &SWARCompressor {
+        self.compressor.as_ref().expect("compressor taken before drop")
+    }
+}
+
+impl DerefMut for PooledCompressor<'_> {
+    fn deref_mut(&mut self) -> &mut SWARCompressor {
+        self.compressor.as_mut().expect("compressor taken before drop")
+    }
+}
+
+impl Drop for PooledCompressor<'_> {
+    fn drop(&mut self) {
+        if let Some(compressor) = self.compressor.take() {
+            self.pool.lock().push(compressor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_pool_compresses_across_threads() {
+        let pool = CompressorPool::new();
+        thread::scope(|s| {
+            for t in 0..8u8 {
+                let pool = &pool;
+                s.spawn(move || {
+                    for i in 0..10 {
+                        let data = format!("thread {t} task {i}, ").repeat(200 + i * 10).into_bytes();
+                        let mut compressor = pool.get();
+                        let compressed = compressor.compress(&data);
+                        assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+                    }
+                });
+            }
+        });
+
+        // Every compressor came back, and no more were made than threads
+        let idle = pool.idle_count();
+        assert!((1..=8).contains(&idle));
+        let held: Vec<_> = (0..idle).map(|_| pool.get()).collect();
+        assert_eq!(pool.idle_count(), 0);
+        drop(held);
+        assert_eq!(pool.idle_count(), idle);
+    }
+}
@@ -0,0 +1,38 @@
+// JavaScript bindings of the Rust backend. Build for the browser with
+// `cargo build --lib --release --target wasm32-unknown-unknown --no-default-features --features wasm`
+// and run `wasm-bindgen` on the output. Data crosses as self-describing
+// frames, so `decompress` needs no separate length.
+
+use alloc::vec::Vec;
+
+use wasm_bindgen::prelude::*;
+
+use crate::frame::{compress_frame, decompress_frame};
+
+/// Compress a `Uint8Array` into a frame readable by `decompress`.
+#[wasm_bindgen]
+pub fn compress(src: &[u8]) -> Vec<u8> {
+    compress_frame(src)
+}
+
+/// Decompress a frame written by `compress`, throwing on corrupt input.
+#[wasm_bindgen]
+pub fn decompress(src: &[u8]) -> Result<Vec<u8>, JsError> {
+    Ok(decompress_frame(src)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    #[test]
+    fn test_wasm_roundtrip() {
+        let original = b"browser roundtrip, browser roundtrip, browser roundtrip".repeat(8);
+        let compressed = compress(&original);
+        assert!(compressed.len() < original.len());
+        assert_eq!(decompress(&compressed).ok(), Some(original));
+        assert_eq!(decompress(&compress(b"")).ok(), Some(Vec::new()));
+    }
+}
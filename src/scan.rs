@@ -0,0 +1,123 @@
+use crate::errors::LzavError;
+use crate::{LZAV_FMT_CUR, LZAV_FMT_MIN};
+
+// LZAV stream format 2 parameters, shared with the C backend
+pub(crate) const LZAV_REF_MIN: usize = 6;
+pub(crate) const LZAV_REF_LEN: usize = LZAV_REF_MIN + 15 + 255 + 254;
+pub(crate) const LZAV_LIT_FIN: usize = 6;
+pub(crate) const LZAV_TINY_MAX: usize = 32;
+// Most output per stream byte: a 4 byte reference block (header, offset
+// byte, two length bytes) yields up to LZAV_REF_LEN + 10 bytes at REF_MIN 15
+pub(crate) const LZAV_MAX_EXPANSION: usize = (LZAV_REF_LEN + 10).div_ceil(4);
+
+/// Streams of up to `LZAV_TINY_MAX` bytes are stored by the C backend as the
+/// prefix byte, the length and the raw bytes.
+#[inline(always)]
+pub(crate) fn is_tiny_stream(src: &[u8]) -> bool {
+    src.len() >= 2 && src[1] as usize <= LZAV_TINY_MAX && src.len() == src[1] as usize + 2
+}
+
+/// Read the variable-length extension of a literal block length, returning
+/// the value and the number of bytes it occupied.
+#[inline(always)]
+pub(crate) fn read_literal_len(src: &[u8]) -> Option<(usize, usize)> {
+    let mut len = 0;
+    let mut sh = 0;
+    for (i, &b) in src.iter().enumerate() {
+        len |= ((b & 0x7F) as usize) << sh;
+        if b & 0x80 == 0 || sh == 28 {
+            return Some((len, i + 1));
+        }
+        sh += 7;
+    }
+    None
+}
+
+/// Read the one or two byte extension of a format 1 literal block length.
+#[inline(always)]
+pub(crate) fn read_literal_len_1(src: &[u8]) -> Option<(usize, usize)> {
+    match *src.first()? {
+        255 => Some((255 + *src.get(1)? as usize, 2)),
+        l2 => Some((l2 as usize, 1)),
+    }
+}
+
+/// Decompressed length the block headers of raw stream `data` describe,
+/// without decoding it. Headers that claim more literals than `data` holds,
+/// or a truncated header, are `SourceOutOfBounds`, so the result never
+/// exceeds `data.len()` times the format's maximum expansion and is safe to
+/// allocate.
+pub(crate) fn stream_size(data: &[u8]) -> Result<usize, LzavError> {
+    if data.is_empty() {
+        return Ok(0);
+    }
+    if data.len() < 2 {
+        return Err(LzavError::SourceOutOfBounds);
+    }
+    if let Some(payload) = crate::stored_payload(data) {
+        return Ok(payload.len());
+    }
+    let fmt = data[0] >> 4;
+    if fmt == LZAV_FMT_CUR && is_tiny_stream(data) {
+        return Ok(data[1] as usize);
+    }
+
+    // Format 1 uses shorter length extensions and a 5 byte tail threshold
+    let ipet = match fmt {
+        LZAV_FMT_CUR => data.len().saturating_sub(LZAV_LIT_FIN),
+        LZAV_FMT_MIN => data.len().saturating_sub(5),
+        _ => return Err(LzavError::UnknownFormat),
+    };
+
+    let mref = (data[0] & 15) as usize;
+    if mref == 0 {
+        return Err(LzavError::UnknownFormat);
+    }
+    let mut pos = 1;
+    let mut total_size = 0;
+    
+    while pos < ipet {
+        let bh = data[pos] as usize;
+        pos += 1;
+
+        if bh & 0x30 == 0 {
+            let mut len = bh & 15;
+            if len == 0 {
+                let ext = if fmt == LZAV_FMT_CUR {
+                    read_literal_len(&data[pos..])
+                } else {
+                    read_literal_len_1(&data[pos..])
+                };
+                let (extra, read) = ext.ok_or(LzavError::SourceOutOfBounds)?;
+                len = extra.saturating_add(16);
+                pos += read;
+            }
+            // Literals are stored verbatim, so they must fit in `data`
+            if len > data.len() - pos {
+                return Err(LzavError::SourceOutOfBounds);
+            }
+            total_size += len;
+            pos += len;
+        } else {
+            pos += (bh >> 4) & 3;
+            let mut len = bh & 15;
+            if len == 0 {
+                let &lb = data.get(pos).ok_or(LzavError::SourceOutOfBounds)?;
+                pos += 1;
+                len = 16 + lb as usize;
+                if lb == 255 && fmt == LZAV_FMT_CUR {
+                    let &lb2 = data.get(pos).ok_or(LzavError::SourceOutOfBounds)?;
+                    pos += 1;
+                    len += lb2 as usize;
+                }
+            }
+            total_size += len + mref - 1;
+        }
+    }
+
+    // Unreachable for well-formed headers; guards the allocation regardless
+    if total_size > data.len().saturating_mul(LZAV_MAX_EXPANSION) {
+        return Err(LzavError::SourceOutOfBounds);
+    }
+    Ok(total_size)
+}
@@ -0,0 +1,96 @@
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::errors::LzavError;
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+
+/// Destination for a compressed stream, so one `compress_into` serves
+/// growable, fixed-size and streaming outputs alike.
+///
+/// Implemented for `&mut Vec<u8>` (appends), `&mut [u8]` (fills from the
+/// front and advances past what it wrote, like `io::Write` for slices) and,
+/// with `std`, `&mut dyn Write`.
+pub trait Sink {
+    /// Why a push can fail: `DestOutOfBounds` for a slice too short, or the
+    /// writer's `io::Error`. Pushing to a `Vec` never fails; its `LzavError`
+    /// carries `compress_into`'s own failures, which every sink's error
+    /// must be able to hold.
+    type Error;
+
+    /// Append all of `bytes`, or nothing when they do not fit.
+    fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+// Lending a sink keeps it usable afterwards, e.g. to see how much of a slice is left
+impl<S: Sink + ?Sized> Sink for &mut S {
+    type Error = S::Error;
+
+    fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), S::Error> {
+        (**self).push_bytes(bytes)
+    }
+}
+
+impl Sink for &mut Vec<u8> {
+    type Error = LzavError;
+
+    fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), LzavError> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+impl Sink for &mut [u8] {
+    type Error = LzavError;
+
+    fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), LzavError> {
+        if bytes.len() > self.len() {
+            return Err(LzavError::DestOutOfBounds);
+        }
+        let (head, tail) = mem::take(self).split_at_mut(bytes.len());
+        head.copy_from_slice(bytes);
+        *self = tail;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Sink for &mut dyn Write {
+    type Error = io::Error;
+
+    fn push_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.write_all(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sink_impls() {
+        let mut vec = b"kept".to_vec();
+        let mut sink = &mut vec;
+        sink.push_bytes(b" and ").unwrap();
+        sink.push_bytes(b"appended").unwrap();
+        assert_eq!(vec, b"kept and appended");
+
+        let mut buf = [0u8; 8];
+        let mut sink = &mut buf[..];
+        sink.push_bytes(b"abc").unwrap();
+        assert_eq!(sink.len(), 5);
+        assert_eq!(sink.push_bytes(b"too long"), Err(LzavError::DestOutOfBounds));
+        sink.push_bytes(b"defgh").unwrap();
+        assert!(sink.is_empty());
+        assert_eq!(&buf, b"abcdefgh");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_writer_sink() {
+        let mut out = Vec::new();
+        let mut sink: &mut dyn Write = &mut out;
+        sink.push_bytes(b"through a writer").unwrap();
+        assert_eq!(out, b"through a writer");
+    }
+}
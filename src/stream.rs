@@ -0,0 +1,423 @@
+use std::io::{self, BufRead, Read, Write};
+
+use crate::{compress, compress_bound, try_decompress};
+
+/// Largest block the stream adapters emit.
+pub use crate::LZAV_WIN_LEN;
+
+// Per-block header: original length then compressed length, both u32 LE
+const BLOCK_HEADER_LEN: usize = 8;
+
+/// Streaming compressor that frames input into independently compressed blocks.
+///
+/// Each block is written as `[original_len: u32le][compressed_len: u32le]`
+/// followed by the compressed bytes, and can be read back with [`LzavDecoder`].
+pub struct LzavEncoder<W: Write> {
+    inner: Option<W>,
+    block: Vec<u8>,
+    block_size: usize,
+    compressed: Vec<u8>,
+}
+
+impl<W: Write> LzavEncoder<W> {
+    /// Create an encoder that emits blocks of up to `LZAV_WIN_LEN` bytes.
+    pub fn new(inner: W) -> Self {
+        Self::with_block_size(inner, LZAV_WIN_LEN)
+    }
+
+    /// Create an encoder with a custom block size, clamped to `1..=LZAV_WIN_LEN`.
+    pub fn with_block_size(inner: W, block_size: usize) -> Self {
+        let block_size = block_size.clamp(1, LZAV_WIN_LEN);
+        Self {
+            inner: Some(inner),
+            block: Vec::with_capacity(block_size),
+            block_size,
+            compressed: Vec::new(),
+        }
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().expect("encoder already finished")
+    }
+
+    /// Emit any buffered data as a block now, even if it is short, without
+    /// flushing the inner writer. Blocks never reference each other, so a
+    /// decoder can turn everything written so far into output without
+    /// waiting for more. Each block starts with an empty window, so frequent
+    /// flushes lower latency at the cost of ratio; small blocks find fewer
+    /// matches and each adds an 8 byte header.
+    pub fn flush_block(&mut self) -> io::Result<()> {
+        self.write_block()
+    }
+
+    /// Emit any buffered data as a final block and return the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_block()?;
+        let mut inner = self.inner.take().expect("encoder already finished");
+        inner.flush()?;
+        Ok(inner)
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+
+        self.compressed.resize(compress_bound(self.block.len() as i32) as usize, 0);
+        let compressed_len = compress(&self.block, &mut self.compressed)?;
+
+        let inner = self.inner.as_mut().expect("encoder already finished");
+        inner.write_all(&(self.block.len() as u32).to_le_bytes())?;
+        inner.write_all(&(compressed_len as u32).to_le_bytes())?;
+        inner.write_all(&self.compressed[..compressed_len])?;
+
+        self.block.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for LzavEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let take = buf.len().min(self.block_size - self.block.len());
+        self.block.extend_from_slice(&buf[..take]);
+
+        if self.block.len() == self.block_size {
+            self.write_block()?;
+        }
+        Ok(take)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_block()?;
+        self.inner.as_mut().expect("encoder already finished").flush()
+    }
+}
+
+impl<W: Write> Drop for LzavEncoder<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            // Errors can't be reported from drop; call `finish` to observe them
+            let _ = self.flush();
+        }
+    }
+}
+
+// Split a block header into its original and compressed lengths
+fn parse_block_header(header: &[u8; BLOCK_HEADER_LEN]) -> io::Result<(usize, usize)> {
+    let original_len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let compressed_len = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+
+    if original_len == 0
+        || original_len > LZAV_WIN_LEN
+        || compressed_len > compress_bound(original_len as i32) as usize
+    {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid block header"));
+    }
+    Ok((original_len, compressed_len))
+}
+
+// A block must decode to exactly the original length its header states
+fn check_block_len(len: usize, original_len: usize) -> io::Result<()> {
+    if len != original_len {
+        let message = format!("block decoded to {} bytes, header says {}", len, original_len);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+    Ok(())
+}
+
+/// Streaming decompressor for the block framing written by [`LzavEncoder`].
+pub struct LzavDecoder<R: Read> {
+    inner: R,
+    compressed: Vec<u8>,
+    block: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> LzavDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            compressed: Vec::new(),
+            block: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Consume the decoder and return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Returns false on a clean end of stream at a block boundary
+    fn read_block(&mut self) -> io::Result<bool> {
+        let mut header = [0u8; BLOCK_HEADER_LEN];
+        let mut filled = 0;
+        while filled < header.len() {
+            match self.inner.read(&mut header[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let (original_len, compressed_len) = parse_block_header(&header)?;
+        self.compressed.resize(compressed_len, 0);
+        self.inner.read_exact(&mut self.compressed)?;
+
+        self.block.resize(original_len, 0);
+        let len = try_decompress(&self.compressed, &mut self.block)?;
+        check_block_len(len, original_len)?;
+        self.pos = 0;
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for LzavDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while self.pos == self.block.len() {
+            if !self.read_block()? {
+                return Ok(0);
+            }
+        }
+
+        let n = buf.len().min(self.block.len() - self.pos);
+        buf[..n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Streaming decompressor for the block framing written by [`LzavEncoder`],
+/// reading from a [`BufRead`].
+///
+/// Blocks already held whole in the reader's buffer are decoded in place,
+/// without the copy [`LzavDecoder`] makes. Decompressed bytes are served
+/// through both `Read` and `BufRead`.
+pub struct LzavReadDecoder<R: BufRead> {
+    inner: R,
+    compressed: Vec<u8>,
+    block: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> LzavReadDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            compressed: Vec::new(),
+            block: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Consume the decoder and return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Returns false on a clean end of stream at a block boundary
+    fn read_block(&mut self) -> io::Result<bool> {
+        if self.inner.fill_buf()?.is_empty() {
+            return Ok(false);
+        }
+
+        let mut header = [0u8; BLOCK_HEADER_LEN];
+        self.inner.read_exact(&mut header)?;
+        let (original_len, compressed_len) = parse_block_header(&header)?;
+
+        self.block.resize(original_len, 0);
+        let buffered = self.inner.fill_buf()?;
+        let len = if buffered.len() >= compressed_len {
+            let len = try_decompress(&buffered[..compressed_len], &mut self.block)?;
+            self.inner.consume(compressed_len);
+            len
+        } else {
+            self.compressed.resize(compressed_len, 0);
+            self.inner.read_exact(&mut self.compressed)?;
+            try_decompress(&self.compressed, &mut self.block)?
+        };
+        check_block_len(len, original_len)?;
+        self.pos = 0;
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> BufRead for LzavReadDecoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.block.len() {
+            if !self.read_block()? {
+                break;
+            }
+        }
+        Ok(&self.block[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.block.len());
+    }
+}
+
+impl<R: BufRead> Read for LzavReadDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let available = self.fill_buf()?;
+        let n = buf.len().min(available.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_data(len: usize) -> Vec<u8> {
+        b"streaming block data with some repetition, "
+            .iter()
+            .cycle()
+            .take(len)
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn test_stream_roundtrip_multiple_blocks() {
+        let original = sample_data(10_000);
+
+        let mut encoder = LzavEncoder::with_block_size(Vec::new(), 1024);
+        encoder.write_all(&original).unwrap();
+        let encoded = encoder.finish().unwrap();
+        assert!(encoded.len() < original.len());
+
+        let mut decoder = LzavDecoder::new(&encoded[..]);
+        let mut decoded = Vec::new();
+        io::copy(&mut decoder, &mut decoded).unwrap();
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn test_stream_flush_and_drop_emit_blocks() {
+        let mut encoded = Vec::new();
+        {
+            let mut encoder = LzavEncoder::new(&mut encoded);
+            encoder.write_all(b"first part, ").unwrap();
+            encoder.flush().unwrap();
+            encoder.write_all(b"second part").unwrap();
+        }
+
+        let mut decoded = Vec::new();
+        LzavDecoder::new(&encoded[..]).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, b"first part, second part");
+    }
+
+    #[test]
+    fn test_stream_flush_block_is_decodable_boundary() {
+        let messages = [&b"ping ping ping"[..], b"a reply to the ping", b"", b"ping again"];
+        let mut encoder = LzavEncoder::new(Vec::new());
+        let mut boundaries = Vec::new();
+        for message in messages {
+            encoder.write_all(message).unwrap();
+            encoder.flush_block().unwrap();
+            boundaries.push(encoder.get_ref().len());
+        }
+        let encoded = encoder.finish().unwrap();
+        assert_eq!(encoded.len(), *boundaries.last().unwrap());
+
+        // Everything up to a boundary decodes alone, and so does each block
+        let mut start = 0;
+        for (i, &end) in boundaries.iter().enumerate() {
+            let mut prefix = Vec::new();
+            LzavDecoder::new(&encoded[..end]).read_to_end(&mut prefix).unwrap();
+            assert_eq!(prefix, messages[..=i].concat());
+
+            let mut block = Vec::new();
+            LzavDecoder::new(&encoded[start..end]).read_to_end(&mut block).unwrap();
+            assert_eq!(block, messages[i]);
+            start = end;
+        }
+    }
+
+    #[test]
+    fn test_stream_truncated_input() {
+        let mut encoder = LzavEncoder::new(Vec::new());
+        encoder.write_all(&sample_data(2048)).unwrap();
+        let encoded = encoder.finish().unwrap();
+
+        let mut decoded = Vec::new();
+        let result = LzavDecoder::new(&encoded[..encoded.len() - 1]).read_to_end(&mut decoded);
+        assert!(result.is_err());
+
+        // A header claiming more bytes than the block decodes to
+        let mut inflated = encoded.clone();
+        inflated[..4].copy_from_slice(&2049u32.to_le_bytes());
+        let result = LzavDecoder::new(&inflated[..]).read_to_end(&mut decoded);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_buf_read_decoder() {
+        let original = sample_data(10_000);
+        let mut encoder = LzavEncoder::with_block_size(Vec::new(), 1000);
+        encoder.write_all(&original).unwrap();
+        let encoded = encoder.finish().unwrap();
+
+        // Small reader buffers split blocks, large ones hold them whole
+        for capacity in [1, 7, 64, 1 << 16] {
+            let reader = io::BufReader::with_capacity(capacity, &encoded[..]);
+            let mut decoder = LzavReadDecoder::new(reader);
+            let mut decoded = Vec::new();
+            io::copy(&mut decoder, &mut decoded).unwrap();
+            assert_eq!(decoded, original);
+        }
+
+        // Reads that straddle the 1000-byte block boundaries
+        let mut decoder = LzavReadDecoder::new(&encoded[..]);
+        let mut decoded = Vec::new();
+        let mut buf = [0u8; 333];
+        loop {
+            let n = decoder.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            decoded.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn test_buf_read_decoder_errors() {
+        let mut encoder = LzavEncoder::new(Vec::new());
+        encoder.write_all(&sample_data(2048)).unwrap();
+        let encoded = encoder.finish().unwrap();
+
+        let mut decoded = Vec::new();
+        let result = LzavReadDecoder::new(&encoded[..encoded.len() - 1]).read_to_end(&mut decoded);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut corrupt = encoded.clone();
+        corrupt[BLOCK_HEADER_LEN] = 0x76;
+        let result = LzavReadDecoder::new(&corrupt[..]).read_to_end(&mut decoded);
+        assert!(result.is_err());
+
+        // A header claiming more bytes than the block decodes to, whether
+        // the block is decoded from the reader's buffer or copied out first
+        let mut inflated = encoded.clone();
+        inflated[..4].copy_from_slice(&2049u32.to_le_bytes());
+        for capacity in [7, 1 << 16] {
+            let reader = io::BufReader::with_capacity(capacity, &inflated[..]);
+            let result = LzavReadDecoder::new(reader).read_to_end(&mut decoded);
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use alloc::alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout};
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+use core::slice;
+
+/// Zeroed heap buffer whose data starts on a 64-byte boundary, the external
+/// scratch type for `compress_with_scratch`. Dereferences to `[u8]`, so it
+/// can be passed wherever a scratch slice is expected.
+#[derive(Debug)]
+pub struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// SAFETY: the buffer owns its allocation exclusively, like a `Vec<u8>`
+unsafe impl Send for AlignedBuffer {}
+unsafe impl Sync for AlignedBuffer {}
+
+impl AlignedBuffer {
+    /// Alignment of the buffer's data in bytes.
+    pub const ALIGN: usize = 64;
+
+    /// Allocate `len` zeroed bytes aligned to [`ALIGN`](Self::ALIGN).
+    pub fn new(len: usize) -> Self {
+        let layout = Self::layout(len);
+        // SAFETY: the layout has a non-zero size
+        let ptr = unsafe { alloc_zeroed(layout) };
+        match NonNull::new(ptr) {
+            Some(ptr) => Self { ptr, len },
+            None => handle_alloc_error(layout),
+        }
+    }
+
+    /// A buffer large enough for `compress_with_scratch` to use for `srcl`
+    /// input bytes, sized by `calculate_hash_table_size`.
+    #[cfg(any(feature = "c-backend", feature = "rust-backend"))]
+    pub fn for_scratch(srcl: i32) -> Self {
+        Self::new(crate::calculate_hash_table_size(srcl))
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        // SAFETY: `ptr` holds `len` initialized bytes for the buffer's lifetime
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: as above, and `&mut self` makes the access unique
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+
+    // Zero-length buffers still allocate one byte so the pointer stays aligned
+    fn layout(len: usize) -> Layout {
+        Layout::from_size_align(len.max(1), Self::ALIGN).expect("buffer length overflows isize")
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        // SAFETY: allocated in `new` with the same layout
+        unsafe { dealloc(self.ptr.as_ptr(), Self::layout(self.len)) }
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
+
+/// Copy `len` elements of `buf` from `src_start` to `dst_start`, which must
+/// be later in `buf`. When the ranges overlap (`dst_start - src_start < len`)
+/// the copy reads elements it wrote itself, repeating the pattern the way an
+/// LZ reference does: a distance of 1 is a run of one element.
+#[inline(always)]
+pub fn copy_overlapping<T: Copy>(buf: &mut [T], dst_start: usize, src_start: usize, len: usize) {
+    let d = dst_start - src_start;
+    if d >= len {
+        buf.copy_within(src_start..src_start + len, dst_start);
+    } else if d >= 8 {
+        // An 8 element step never reads what the same step writes
+        let mut i = 0;
+        while len - i >= 8 {
+            buf.copy_within(src_start + i..src_start + i + 8, dst_start + i);
+            i += 8;
+        }
+        buf.copy_within(src_start + i..src_start + len, dst_start + i);
+    } else {
+        for i in 0..len {
+            buf[dst_start + i] = buf[src_start + i];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Byte-at-a-time reference copy the helper must match
+    fn naive_copy(buf: &mut [u8], dst_start: usize, src_start: usize, len: usize) {
+        for i in 0..len {
+            buf[dst_start + i] = buf[src_start + i];
+        }
+    }
+
+    #[test]
+    fn test_copy_overlapping() {
+        let mut run = *b"a..........";
+        copy_overlapping(&mut run, 1, 0, 10);
+        assert_eq!(&run, b"aaaaaaaaaaa");
+
+        let mut period3 = *b"abc...........";
+        copy_overlapping(&mut period3, 3, 0, 11);
+        assert_eq!(&period3, b"abcabcabcabcab");
+
+        let mut disjoint = *b"abcdef......";
+        copy_overlapping(&mut disjoint, 6, 0, 6);
+        assert_eq!(&disjoint, b"abcdefabcdef");
+
+        let pattern: Vec<u8> = (0..=255).collect();
+        for d in 1..40 {
+            for len in 0..70 {
+                let mut expected = pattern.clone();
+                naive_copy(&mut expected, 50, 50 - d, len);
+                let mut buf = pattern.clone();
+                copy_overlapping(&mut buf, 50, 50 - d, len);
+                assert_eq!(buf, expected, "distance {d}, length {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_aligned_buffer_alignment() {
+        for len in [0, 1, 63, 64, 4096, 1 << 20] {
+            let mut buf = AlignedBuffer::new(len);
+            assert_eq!(buf.as_slice().as_ptr() as usize % 64, 0);
+            assert_eq!(buf.len(), len);
+            assert!(buf.iter().all(|&b| b == 0));
+            buf.as_mut_slice().fill(0xAB);
+        }
+    }
+
+    #[cfg(any(feature = "c-backend", feature = "rust-backend"))]
+    #[test]
+    fn test_aligned_buffer_as_scratch() {
+        let original = b"scratch in an aligned buffer, scratch in an aligned buffer".repeat(100);
+        let mut scratch = AlignedBuffer::for_scratch(original.len() as i32);
+        let mut compressed = vec![0u8; crate::compress_bound(original.len() as i32) as usize];
+
+        let len = crate::compress_with_scratch(&original, &mut compressed, &mut scratch);
+        assert!(len > 0);
+        assert_eq!(
+            crate::decompress_to_vec(&compressed[..len as usize], original.len()).unwrap(),
+            original
+        );
+    }
+}